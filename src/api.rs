@@ -1,39 +1,297 @@
-use reqwest::blocking::Client;
+use std::thread;
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::Url;
+use reqwest::header::HeaderMap;
 use serde::{Deserialize, Serialize};
 use serde_json::{self, json, Value};
-use simple_error::{SimpleError};
 use chrono::{DateTime, Utc, TimeZone};
 use chrono_humanize::HumanTime;
 use chrono::prelude::*;
+use uuid::Uuid;
+use crate::error::Error;
+use crate::oncalendar::{parse_tz, OnCalendar};
+use crate::retry::RetryPolicy;
 
+/// A check as returned by the management API
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Check {
+    // read-only API keys get unique_key instead of uuid and URLs
+    #[serde(default)]
     pub uuid: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unique_key: Option<String>,
+    /// Abbreviated UUID shown by the CLI, see `set_short_uuids`
     #[serde(skip)]
     pub short_uuid: String,
     pub slug: String,
     pub name: String,
+    #[serde(default)]
+    pub desc: String,
+    #[serde(default)]
     pub ping_url: String,
+    #[serde(default)]
     pub pause_url: String,
     pub last_ping: Option<String>,
     pub next_ping: Option<String>,
     pub grace: u32,
     pub n_pings: u32,
     pub tags: String,
-    pub timeout: Option<u32>,
-    pub tz:  Option<String>,
-    pub schedule: Option<String>,
+    /// Period or schedule, from the `timeout`, `tz` and `schedule` fields
+    #[serde(flatten)]
+    pub schedule: ScheduleKind,
     pub status: String,
+    #[serde(default)]
     pub update_url: String
 }
 
+/// How a check expects its pings
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "RawSchedule", into = "RawSchedule")]
+#[non_exhaustive]
+pub enum ScheduleKind {
+    /// A ping at least every `timeout` seconds
+    Simple { timeout: u32 },
+    /// Pings at times given by a cron expression, in time zone `tz`
+    Cron { expr: String, tz: String },
+    /// Pings at times given by a systemd OnCalendar expression, see `OnCalendar`
+    OnCalendar { expr: String, tz: String },
+}
+
+// Schedule as in the API, simple checks have no schedule
+#[derive(Serialize, Deserialize)]
+struct RawSchedule {
+    #[serde(default)]
+    timeout: Option<u32>,
+    #[serde(default)]
+    tz: Option<String>,
+    #[serde(default)]
+    schedule: Option<String>,
+}
+
+impl From<RawSchedule> for ScheduleKind {
+    fn from(raw: RawSchedule) -> ScheduleKind {
+        match raw.schedule {
+            Some(expr) => ScheduleKind::from_expr(&expr, raw.tz.as_deref().unwrap_or("UTC")),
+            None => ScheduleKind::Simple { timeout: raw.timeout.unwrap_or(0) }
+        }
+    }
+}
+
+impl From<ScheduleKind> for RawSchedule {
+    fn from(kind: ScheduleKind) -> RawSchedule {
+        match kind {
+            ScheduleKind::Simple { timeout } => RawSchedule { timeout: Some(timeout), tz: None, schedule: None },
+            ScheduleKind::Cron { expr, tz } | ScheduleKind::OnCalendar { expr, tz } => {
+                RawSchedule { timeout: None, tz: Some(tz), schedule: Some(expr) }
+            }
+        }
+    }
+}
+
+// Cron expressions are single lines of 5 fields, OnCalendar ones have fewer
+// fields and times written with colons
+fn is_cron(expr: &str) -> bool {
+    !expr.contains(['\n', ':']) && expr.split_whitespace().count() == 5
+}
+
+impl ScheduleKind {
+    /// Cron or OnCalendar schedule, depending on how `expr` looks, as healthchecks tells them apart
+    pub fn from_expr(expr: &str, tz: &str) -> ScheduleKind {
+        let (expr, tz) = (expr.to_string(), tz.to_string());
+        if is_cron(&expr) {
+            ScheduleKind::Cron { expr, tz }
+        } else {
+            ScheduleKind::OnCalendar { expr, tz }
+        }
+    }
+
+    /// Period in seconds of simple checks
+    pub fn timeout(&self) -> Option<u32> {
+        match self {
+            ScheduleKind::Simple { timeout } => Some(*timeout),
+            _ => None
+        }
+    }
+
+    /// Cron or OnCalendar expression
+    pub fn expr(&self) -> Option<&str> {
+        match self {
+            ScheduleKind::Simple { .. } => None,
+            ScheduleKind::Cron { expr, .. } | ScheduleKind::OnCalendar { expr, .. } => Some(expr)
+        }
+    }
+
+    /// Time zone of cron and OnCalendar schedules
+    pub fn tz(&self) -> Option<&str> {
+        match self {
+            ScheduleKind::Simple { .. } => None,
+            ScheduleKind::Cron { tz, .. } | ScheduleKind::OnCalendar { tz, .. } => Some(tz)
+        }
+    }
+}
+
+/// A single ping received by a check
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Ping {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub date: String,
+    pub n: u32,
+    #[serde(default)]
+    pub scheme: String,
+    #[serde(default)]
+    pub remote_addr: String,
+    #[serde(default)]
+    pub method: String,
+    #[serde(default)]
+    pub ua: String,
+    #[serde(default)]
+    pub duration: Option<f64>,
+    #[serde(default)]
+    pub body_url: Option<String>,
+    #[serde(default)]
+    pub rid: Option<String>,
+    #[serde(default)]
+    pub exitstatus: Option<i32>,
+}
+
+impl Ping {
+    /// Time the ping was received, in local time zone
+    pub fn date_at(&self) -> DateTime<Local> {
+        parse_datetime(&Some(self.date.clone())).unwrap_or_else(|_| default_datetime())
+    }
+}
+
+/// A change of check's status between up and down
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Flip {
+    pub timestamp: String,
+    #[serde(deserialize_with = "deserialize_up")]
+    pub up: bool,
+}
+
+// The API reports flip direction as 0/1
+fn deserialize_up<'de, D: serde::Deserializer<'de>>(d: D) -> Result<bool, D::Error> {
+    match Value::deserialize(d)? {
+        Value::Bool(b) => Ok(b),
+        Value::Number(n) => Ok(n.as_u64() != Some(0)),
+        v => Err(serde::de::Error::custom(format!("invalid flip direction: {}", v)))
+    }
+}
+
+impl Flip {
+    /// Time of the flip, in local time zone
+    pub fn timestamp_at(&self) -> DateTime<Local> {
+        parse_datetime(&Some(self.timestamp.clone())).unwrap_or_else(|_| default_datetime())
+    }
+}
+
+/// Availability of a check over a period of time, durations are in seconds
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Uptime {
+    /// Percentage of time the check was up
+    pub uptime: f64,
+    pub outages: u32,
+    pub downtime: i64,
+    pub longest_outage: i64,
+    /// Mean time to recovery
+    pub mttr: i64,
+}
+
+impl Uptime {
+    /// Computes uptime between `start` and `end` from check's flips, `up_now` is
+    /// the check's state to assume when there were no flips in that period
+    pub fn from_flips(flips: &[Flip], start: DateTime<Local>, end: DateTime<Local>, up_now: bool) -> Uptime {
+        let mut flips: Vec<(DateTime<Local>, bool)> = flips.iter()
+            .map(|f| (f.timestamp_at(), f.up))
+            .filter(|(ts, _)| *ts > start && *ts <= end)
+            .collect();
+        flips.sort_by_key(|(ts, _)| *ts);
+
+        let mut up = flips.first().map(|(_, up)| !up).unwrap_or(up_now);
+        let mut since = start;
+        let mut outages = Vec::new();
+
+        for (ts, flip_up) in flips {
+            if flip_up == up {
+                continue
+            }
+            if !up {
+                outages.push((ts - since).num_seconds());
+            }
+            up = flip_up;
+            since = ts;
+        }
+        if !up {
+            outages.push((end - since).num_seconds());
+        }
+
+        let total = (end - start).num_seconds().max(1);
+        let downtime: i64 = outages.iter().sum();
+
+        Uptime {
+            uptime: 100.0 * (total - downtime) as f64 / total as f64,
+            outages: outages.len() as u32,
+            downtime,
+            longest_outage: outages.iter().copied().max().unwrap_or(0),
+            mttr: if outages.is_empty() { 0 } else { downtime / outages.len() as i64 },
+        }
+    }
+}
+
+/// Kind of signal sent with a ping
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PingKind {
+    Success,
+    Start,
+    Fail,
+    Log,
+    ExitStatus(u8)
+}
+
+impl PingKind {
+    fn suffix(&self) -> String {
+        match self {
+            PingKind::Success => "".to_string(),
+            PingKind::Start => "/start".to_string(),
+            PingKind::Fail => "/fail".to_string(),
+            PingKind::Log => "/log".to_string(),
+            PingKind::ExitStatus(code) => format!("/{}", code),
+        }
+    }
+}
+
+/// Fields to change with `ApiClient::update`, `None` leaves the field as is
+#[derive(Debug, Clone, Default)]
+pub struct CheckPatch {
+    pub name: Option<String>,
+    pub desc: Option<String>,
+    /// Cron or OnCalendar schedule, turns a simple check into a scheduled one
+    pub schedule: Option<String>,
+    /// Period in seconds, turns a cron check into a simple one
+    pub timeout: Option<u32>,
+    /// Grace in seconds
+    pub grace: Option<u32>,
+    pub tz: Option<String>,
+    pub tags: Option<String>,
+}
+
+impl CheckPatch {
+    /// True if no field is to be changed
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.desc.is_none() && self.schedule.is_none() &&
+            self.timeout.is_none() && self.grace.is_none() && self.tz.is_none() && self.tags.is_none()
+    }
+}
+
 fn default_datetime() -> DateTime<Local> {
     let local: DateTime<Local> = Local::now();
     let tz = local.timezone();
-    Utc.with_ymd_and_hms(1901, 01, 01, 0, 0, 0).unwrap().with_timezone(&tz)
+    Utc.with_ymd_and_hms(1901, 1, 1, 0, 0, 0).unwrap().with_timezone(&tz)
 }
 
-fn parse_datetime(ts: &Option<String>) -> Result<DateTime<Local>, SimpleError> {
+fn parse_datetime(ts: &Option<String>) -> Result<DateTime<Local>, Error> {
     let local: DateTime<Local> = Local::now();
     let tz = local.timezone();
 
@@ -43,15 +301,16 @@ fn parse_datetime(ts: &Option<String>) -> Result<DateTime<Local>, SimpleError> {
 
     let ts = ts.as_ref().unwrap();
     let dt = ts.parse::<DateTime<Utc>>()
-        .map_err(|e| err(format!("Failed to parse datetime: {}", e)))?;
+        .map_err(Error::decode)?;
     Ok(dt.with_timezone(&tz))
 }
 
 fn humanize_datetime(dt: DateTime<Local>) -> String {
-    return format!("{}", HumanTime::from(dt));
+    format!("{}", HumanTime::from(dt))
 }
 
 impl Check {
+    /// Time of the last ping, year 1901 if the check was never pinged
     pub fn last_ping_at(&self) -> DateTime<Local> {
         parse_datetime(&self.last_ping).unwrap_or_else(|_| default_datetime())
     }
@@ -65,159 +324,563 @@ impl Check {
         humanize_datetime(last_ping)
     }
 
+    pub fn next_ping_at(&self) -> DateTime<Local> {
+        parse_datetime(&self.next_ping).unwrap_or_else(|_| default_datetime())
+    }
+
+    pub fn humanized_next_ping_at(&self) -> String {
+        // New and paused checks do not expect pings
+        if self.next_ping.is_none() {
+            return "-".to_string();
+        }
+        humanize_datetime(self.next_ping_at())
+    }
+
+    /// Checks if `tag` is one of check's space separated tags
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.split_whitespace().any(|t| t == tag)
+    }
+
+    /// UUID, or unique key of checks listed with read-only API key, either one
+    /// addresses the check in API URLs
+    pub fn id(&self) -> &str {
+        match &self.unique_key {
+            Some(key) if self.uuid.is_empty() => key,
+            _ => &self.uuid
+        }
+    }
+
+    /// Sets `short_uuid` to the first UUID segment, see `set_short_uuids` for
+    /// IDs unique within a list of checks
     pub fn set_short_uuid(&mut self) {
+        if self.uuid.is_empty() {
+            self.short_uuid = self.id().chars().take(8).collect();
+            return
+        }
+
         if let Some(id) = self.uuid.split('-').next() {
             self.short_uuid = id.to_string()
         }
     }
 }
 
-const BASE_URL: &str = "https://healthchecks.io/api/v3/checks/";
-const SECONDS_PER_HOUR: u32 = 3600;
-const HOURS_PER_YEAR: u32 = 24 * 365; // 8760 hours (365 days)
+pub(crate) const BASE_URL: &str = "https://healthchecks.io/api/v3/checks/";
+/// Ping endpoint of healthchecks.io
+pub const PING_BASE_URL: &str = "https://hc-ping.com/";
+// Limits of both period and grace, as enforced by healthchecks
+const MIN_PERIOD: u32 = 60;
+const MAX_PERIOD: u32 = 365 * 24 * 3600;
+
+fn validate_name(name: &str) -> Result<(), Error> {
+    if name.trim().is_empty() {
+        return Err(Error::Invalid("Check name cannot be empty".to_string()));
+    }
+    Ok(())
+}
+
+// Period of simple checks and grace of all checks, in seconds
+fn validate_period(what: &str, secs: u32) -> Result<(), Error> {
+    if !(MIN_PERIOD..=MAX_PERIOD).contains(&secs) {
+        return Err(Error::Invalid(format!("{} must be between 1 minute and 365 days (inclusive)", what)));
+    }
+    Ok(())
+}
+
+fn validate_cron(expr: &str) -> Result<(), Error> {
+    if expr.split_whitespace().count() != 5 {
+        return Err(Error::Invalid(format!("Invalid cron expression: {} (expected 5 fields)", expr)));
+    }
+    Ok(())
+}
+
+fn validate_schedule(schedule: &ScheduleKind) -> Result<(), Error> {
+    match schedule {
+        ScheduleKind::Simple { timeout } => validate_period("Period", *timeout),
+        ScheduleKind::Cron { expr, tz } => {
+            validate_cron(expr)?;
+            parse_tz(tz).map(|_| ())
+        }
+        ScheduleKind::OnCalendar { expr, tz } => {
+            OnCalendar::parse(expr)?;
+            parse_tz(tz).map(|_| ())
+        }
+    }
+}
+
+/// Checks if `id` is a full, hyphenated check UUID
+pub fn is_uuid(id: &str) -> bool {
+    id.len() == 36 && Uuid::try_parse(id).is_ok()
+}
+
+/// Checks if `id` is a read-only key of a check, 40 hex digits
+pub fn is_unique_key(id: &str) -> bool {
+    id.len() == 40 && id.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+pub(crate) fn api_headers(api_key: &str) -> Result<HeaderMap, Error> {
+    let mut headers = HeaderMap::new();
+    if !api_key.is_empty() {
+        let value = api_key.parse()
+            .map_err(|_| Error::Invalid("Invalid API key, it contains characters not allowed in HTTP headers".to_string()))?;
+        headers.insert("X-Api-Key", value);
+    }
+    Ok(headers)
+}
+
+pub(crate) fn add_request(name: &str, schedule: &ScheduleKind, grace: u32, tags: Option<&str>) -> Result<Value, Error> {
+    validate_name(name)?;
+    validate_schedule(schedule)?;
+    validate_period("Grace period", grace)?;
+
+    let mut c = json!({
+        "name":  name,
+        "grace": grace,
+        "tags": tags.unwrap_or(""),
+        "unique": [ "name" ]
+    });
+    match schedule {
+        ScheduleKind::Simple { timeout } => {
+            c["timeout"] = json!(timeout);
+        }
+        ScheduleKind::Cron { expr, tz } | ScheduleKind::OnCalendar { expr, tz } => {
+            c["schedule"] = json!(expr);
+            c["tz"] = json!(tz);
+        }
+    }
+    Ok(c)
+}
+
+pub(crate) fn update_request(patch: CheckPatch) -> Result<Value, Error> {
+    if patch.is_empty() {
+        return Err(Error::Invalid("Nothing to update".to_string()));
+    }
+    if let Some(name) = &patch.name {
+        validate_name(name)?;
+    }
+    if patch.schedule.is_some() && patch.timeout.is_some() {
+        return Err(Error::Invalid("Check can have either a schedule or a period, not both".to_string()));
+    }
+    if let Some(schedule) = &patch.schedule {
+        validate_schedule(&ScheduleKind::from_expr(schedule, patch.tz.as_deref().unwrap_or("UTC")))?;
+    }
+    if let Some(tz) = &patch.tz {
+        parse_tz(tz)?;
+    }
+    if let Some(timeout) = patch.timeout {
+        validate_period("Period", timeout)?;
+    }
+    if let Some(grace) = patch.grace {
+        validate_period("Grace period", grace)?;
+    }
+
+    // Send only the changed fields, the API leaves the others untouched
+    let mut c = serde_json::Map::new();
+    if let Some(name) = patch.name {
+        c.insert("name".to_string(), json!(name));
+    }
+    if let Some(desc) = patch.desc {
+        c.insert("desc".to_string(), json!(desc));
+    }
+    if let Some(schedule) = patch.schedule {
+        c.insert("schedule".to_string(), json!(schedule));
+    }
+    if let Some(timeout) = patch.timeout {
+        c.insert("timeout".to_string(), json!(timeout));
+    }
+    if let Some(grace) = patch.grace {
+        c.insert("grace".to_string(), json!(grace));
+    }
+    if let Some(tz) = patch.tz {
+        c.insert("tz".to_string(), json!(tz));
+    }
+    if let Some(tags) = patch.tags {
+        c.insert("tags".to_string(), json!(tags));
+    }
+    Ok(Value::Object(c))
+}
+
+pub(crate) fn ping_request_url(ping_url: &str, kind: PingKind, rid: Option<Uuid>) -> Result<Url, Error> {
+    let mut url = Url::parse(ping_url)
+        .map_err(|e| Error::Invalid(format!("{}: invalid ping URL: {}", ping_url, e)))?;
 
-fn err(msg: String) -> SimpleError {
-    SimpleError::new(msg)
+    let path = format!("{}{}", url.path(), kind.suffix());
+    url.set_path(&path);
+    if let Some(rid) = rid {
+        url.query_pairs_mut().append_pair("rid", &rid.to_string());
+    }
+    Ok(url)
+}
+
+pub(crate) fn flips_url(base_url: &str, check: &Check, since: Option<DateTime<Local>>) -> String {
+    let url = format!("{}{}/flips/", base_url, check.id());
+    match since {
+        Some(since) => format!("{}?start={}", url, since.timestamp()),
+        None => url
+    }
+}
+
+// Checks from the list response, optionally only those matching `query`
+pub(crate) fn parse_checks(v: Value, query: Option<&str>) -> Result<Vec<Check>, Error> {
+    let mut checks: Vec<Check> = serde_json::from_value(v["checks"].clone())
+        .map_err(Error::decode)?;
+
+    // short IDs must be unique among all checks, not only the listed ones
+    set_short_uuids(&mut checks);
+
+    if let Some(q) = query {
+        checks.retain(|c| c.slug.contains(q) || c.name.contains(q) || c.uuid.contains(q));
+    }
+
+    Ok(checks)
 }
 
+pub(crate) fn found(result: Result<(Check, MatchKind), Error>) -> Result<Option<Check>, Error> {
+    match result {
+        Ok((check, _)) => Ok(Some(check)),
+        Err(Error::NotFound(_)) => Ok(None),
+        Err(e) => Err(e)
+    }
+}
+
+/// Blocking client of the management and ping APIs
 pub struct ApiClient {
     client: Client,
-    pub base_url: String
+    // pings are sent without the API key
+    ping_client: Client,
+    pub base_url: String,
+    pub ping_base_url: String,
+    /// Retrying of pings and idempotent requests, none by default
+    pub retry: RetryPolicy
 }
 
 impl ApiClient {
-    pub fn new(api_key: &str, base_url: Option<&str>) -> ApiClient {
-        let mut headers = reqwest::header::HeaderMap::new();
-        headers.insert("X-Api-Key", api_key.parse().unwrap());
-
+    /// Creates client using given API key (empty key for pings only) and URL of
+    /// the checks endpoint, healthchecks.io when `None`. Fails with `Error::Invalid`
+    /// for keys which can't be sent in a header, e.g. with a trailing newline
+    pub fn new(api_key: &str, base_url: Option<&str>) -> Result<ApiClient, Error> {
         let client = Client::builder()
-            .default_headers(headers)
+            .default_headers(api_headers(api_key)?)
             .timeout(std::time::Duration::from_secs(30))
             .build()
-            .unwrap();
+            .map_err(Error::network)?;
 
-        ApiClient {
-            client: client,
-            base_url: base_url.unwrap_or(BASE_URL).to_string()
-        }
+        let ping_client = Client::builder()
+            .timeout(std::time::Duration::from_secs(30))
+            .build()
+            .map_err(Error::network)?;
+
+        Ok(ApiClient {
+            client,
+            ping_client,
+            base_url: base_url.unwrap_or(BASE_URL).to_string(),
+            ping_base_url: PING_BASE_URL.to_string(),
+            retry: RetryPolicy::none()
+        })
     }
 
-    pub fn add(&self, name: &str, schedule: &str, grace: u32, tz: Option<&str>, tags: Option<&str>) -> Result<Check, SimpleError> {
-        // Validate inputs
-        if name.trim().is_empty() {
-            return Err(err("Check name cannot be empty".to_string()));
-        }
-        if grace < 1 || grace > HOURS_PER_YEAR {
-            return Err(err(format!("Grace period must be between 1 and {} hours (inclusive)", HOURS_PER_YEAR)));
+    // Sends request, retrying it according to the retry policy. Only for pings
+    // and idempotent requests, a lost response could make others apply twice
+    fn send_retrying(&self, request: RequestBuilder) -> Result<Response, Error> {
+        let mut attempt = 0;
+        loop {
+            let result = request.try_clone()
+                .expect("request with streamed body")
+                .send()
+                .map_err(Error::network)
+                .and_then(Error::check_status);
+
+            match result {
+                Err(e) => match self.retry.delay(attempt, &e) {
+                    Some(delay) => thread::sleep(delay),
+                    None => return Err(e)
+                },
+                response => return response
+            }
+            attempt += 1;
         }
+    }
 
-        let tz_val = tz.unwrap_or("UTC");
-        let tags_val = tags.unwrap_or("");
+    /// Ping URL of check with given UUID
+    pub fn uuid_ping_url(&self, uuid: &str) -> String {
+        format!("{}{}", self.ping_base_url, uuid)
+    }
 
-        let c = json!({
-            "name":  name,
-            "schedule": schedule,
-            "grace": grace * SECONDS_PER_HOUR,
-            "tags": tags_val,
-            "tz": tz_val,
-            "unique": [ "name" ]
-        });
+    /// Creates a check, or returns the existing one with the same name. Grace is in seconds
+    pub fn add(&self, name: &str, schedule: &ScheduleKind, grace: u32, tags: Option<&str>) -> Result<Check, Error> {
+        let c = add_request(name, schedule, grace, tags)?;
 
         let mut check: Check = self.client
             .post(&self.base_url)
             .json(&c)
             .send()
-            .map_err(|e| err(format!("request failed with {:?}", e)))?
-            .error_for_status()
-            .map_err(|e| err(format!("API error: {:?}", e)))?
+            .map_err(Error::network)
+            .and_then(Error::check_status)?
             .json()
-            .map_err(|e| err(format!("Failed to parse response: {}", e)))?;
+            .map_err(Error::decode)?;
 
         check.set_short_uuid();
         Ok(check)
     }
 
-    pub fn delete(&self, check: &Check) -> Result<Check, SimpleError> {
+    /// Changes check's fields given in `patch`
+    pub fn update(&self, check: &Check, patch: CheckPatch) -> Result<Check, Error> {
+        let c = update_request(patch)?;
+
+        let mut check: Check = self.client
+            .post(&check.update_url)
+            .json(&c)
+            .send()
+            .map_err(Error::network)
+            .and_then(Error::check_status)?
+            .json()
+            .map_err(Error::decode)?;
+
+        check.set_short_uuid();
+        Ok(check)
+    }
+
+    /// Deletes a check, returning it as it was before
+    pub fn delete(&self, check: &Check) -> Result<Check, Error> {
         let url = format!("{}{}", self.base_url, check.uuid);
 
         let mut check: Check = self.client
             .delete(&url)
             .send()
-            .map_err(|e| err(format!("request failed with {:?}", e)))?
-            .error_for_status()
-            .map_err(|e| err(format!("API error: {:?}", e)))?
+            .map_err(Error::network)
+            .and_then(Error::check_status)?
             .json()
-            .map_err(|e| err(format!("Failed to parse response: {}", e)))?;
+            .map_err(Error::decode)?;
 
         check.set_short_uuid();
         Ok(check)
     }
 
-    pub fn ping(&self, check: &Check) -> Result<(), SimpleError> {
-        self.client
-            .get(&check.ping_url)
-            .send()
-            .map_err(|e| err(format!("request failed with {:?}", e)))?
-            .error_for_status()
-            .map_err(|e| err(format!("API error: {:?}", e)))?;
+    /// Sends a success ping
+    pub fn ping(&self, check: &Check) -> Result<(), Error> {
+        self.ping_with(check, PingKind::Success, None, None)
+    }
 
+    /// Sends a ping of given kind, with optional body and run ID pairing start and end of a job
+    pub fn ping_with(&self, check: &Check, kind: PingKind, body: Option<&[u8]>, rid: Option<Uuid>) -> Result<(), Error> {
+        self.ping_url(&check.ping_url, kind, body, rid)
+    }
+
+    /// Ping URL of check with given slug in project with given ping key, the check is
+    /// created on first ping if `create` is set
+    pub fn slug_ping_url(&self, ping_key: &str, slug: &str, create: bool) -> String {
+        let url = format!("{}{}/{}", self.ping_base_url, ping_key, slug);
+        if create {
+            return format!("{}?create=1", url);
+        }
+        url
+    }
+
+    /// Like `ping_with`, but pings `ping_url` directly, without looking up the check
+    pub fn ping_url(&self, ping_url: &str, kind: PingKind, body: Option<&[u8]>, rid: Option<Uuid>) -> Result<(), Error> {
+        let url = ping_request_url(ping_url, kind, rid)?;
+
+        let request = match body {
+            Some(body) => self.ping_client.post(url).body(body.to_vec()),
+            None => self.ping_client.get(url)
+        };
+
+        self.send_retrying(request)?;
         Ok(())
     }
 
-    pub fn pause(&self, check: &Check) -> Result<Check, SimpleError> {
+    /// Pauses monitoring of a check until its next ping
+    pub fn pause(&self, check: &Check) -> Result<Check, Error> {
         let url = format!("{}{}/pause", self.base_url, check.uuid);
 
-        let mut check: Check = self.client
-            .post(&url)
-            .send()
-            .map_err(|e| err(format! ("request failed with {:?}", e)))?
-            .error_for_status()
-            .map_err(|e| err(format!("API error: {:?}", e)))?
+        let mut check: Check = self.send_retrying(self.client.post(&url))?
             .json()
-            .map_err(|e| err(format!("Failed to parse response: {}", e)))?;
+            .map_err(Error::decode)?;
 
         check.set_short_uuid();
         Ok(check)
     }
 
-    pub fn get(&self, query: Option<&str>) -> Result<Vec<Check>, SimpleError> {
-        let v: Value = self.client
-            .get(&self.base_url)
-            .send()
-            .map_err(|e| err(format!("request failed with {:?}", e)))?
-            .error_for_status()
-            .map_err(|e| err(format!("API error: {:?}", e)))?
+    /// Resumes a paused check
+    pub fn resume(&self, check: &Check) -> Result<Check, Error> {
+        let url = format!("{}{}/resume", self.base_url, check.uuid);
+
+        let mut check: Check = self.send_retrying(self.client.post(&url))?
+            .json()
+            .map_err(Error::decode)?;
+
+        check.set_short_uuid();
+        Ok(check)
+    }
+
+    /// Lists check's recent pings, newest first
+    pub fn pings(&self, check: &Check) -> Result<Vec<Ping>, Error> {
+        let url = format!("{}{}/pings/", self.base_url, check.uuid);
+
+        let v: Value = self.send_retrying(self.client.get(&url))?
+            .json()
+            .map_err(Error::decode)?;
+
+        serde_json::from_value(v["pings"].clone()).map_err(Error::decode)
+    }
+
+    /// Body of the ping number `n`
+    pub fn ping_body(&self, check: &Check, n: u32) -> Result<Vec<u8>, Error> {
+        let url = format!("{}{}/pings/{}/body", self.base_url, check.uuid, n);
+
+        let body = self.send_retrying(self.client.get(&url))?
+            .bytes()
+            .map_err(Error::decode)?;
+
+        Ok(body.to_vec())
+    }
+
+    /// Lists check's status changes, optionally since given time
+    pub fn flips(&self, check: &Check, since: Option<DateTime<Local>>) -> Result<Vec<Flip>, Error> {
+        let url = flips_url(&self.base_url, check, since);
+
+        let flips: Vec<Flip> = self.send_retrying(self.client.get(&url))?
+            .json()
+            .map_err(Error::decode)?;
+
+        Ok(flips)
+    }
+
+    /// Lists all checks, or those whose slug, name or UUID contains `query`
+    pub fn get(&self, query: Option<&str>) -> Result<Vec<Check>, Error> {
+        self.get_tagged(query, &[])
+    }
+
+    /// Like `get`, but lists only checks having all of the `tags`, filtered by the server
+    pub fn get_tagged(&self, query: Option<&str>, tags: &[&str]) -> Result<Vec<Check>, Error> {
+        let params: Vec<(&str, &str)> = tags.iter().map(|t| ("tag", *t)).collect();
+
+        let v: Value = self.send_retrying(self.client.get(&self.base_url).query(&params))?
+            .json()
+            .map_err(Error::decode)?;
+
+        parse_checks(v, query)
+    }
+
+    /// Fetches a single check by its UUID or, with read-only API key, by its unique key
+    pub fn get_check(&self, id: &str) -> Result<Check, Error> {
+        let url = format!("{}{}", self.base_url, id);
+
+        let mut check: Check = self.send_retrying(self.client.get(&url))
+            .map_err(|e| e.not_found(id))?
             .json()
-            .map_err(|e| err(format!("Failed to parse response: {}", e)))?;
+            .map_err(Error::decode)?;
 
-        let ref checks_ref = Value::to_string(&v["checks"]);
-        let mut checks: Vec<Check> = serde_json::from_str(checks_ref)
-            .map_err(|e| err(format!("JSON: {}", e.to_string())))?;
+        check.set_short_uuid();
+        Ok(check)
+    }
 
-        if let Some(q) = query {
-            checks.retain(|c| c.slug.contains(q) || c.name.contains(q) || c.uuid.contains(q));
+    /// Resolves `id` to a single check, see `resolve_id`. Full UUIDs and
+    /// unique keys are fetched directly, without listing all checks
+    pub fn resolve(&self, id: &str) -> Result<(Check, MatchKind), Error> {
+        if is_uuid(id) || is_unique_key(id) {
+            return Ok((self.get_check(id)?, MatchKind::Uuid));
         }
 
-        for c in &mut checks {
-            c.set_short_uuid()
+        let checks = self.get(None)?;
+        resolve_id(id, &checks).into_result(id)
+    }
+
+    /// Like `resolve`, but a missing check is `None` rather than an error
+    pub fn find(&self, id: &str) -> Result<Option<Check>, Error> {
+        found(self.resolve(id))
+    }
+}
+
+/// How an ID matched a check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MatchKind {
+    Uuid,
+    UuidPrefix,
+    Slug,
+    Name,
+    Substring
+}
+
+/// Result of resolving an ID against a list of checks
+#[derive(Debug, Clone)]
+pub enum Resolution {
+    Found(Box<Check>, MatchKind),
+    NotFound,
+    Ambiguous(Vec<Check>)
+}
+
+impl Resolution {
+    /// Found check, or `Error::NotFound` or `Error::Ambiguous` for `id`
+    pub fn into_result(self, id: &str) -> Result<(Check, MatchKind), Error> {
+        match self {
+            Resolution::Found(check, kind) => Ok((*check, kind)),
+            Resolution::NotFound => Err(Error::NotFound(id.to_string())),
+            Resolution::Ambiguous(candidates) => Err(Error::Ambiguous { id: id.to_string(), candidates }),
         }
+    }
+}
 
-        Ok(checks)
+impl MatchKind {
+    /// Checks if `id` matches check `c` this way
+    pub fn matches(&self, id: &str, c: &Check) -> bool {
+        match self {
+            MatchKind::Uuid => c.uuid == id,
+            MatchKind::UuidPrefix => is_uuid_prefix(id) && c.uuid.starts_with(id),
+            MatchKind::Slug => c.slug == id,
+            MatchKind::Name => c.name == id,
+            MatchKind::Substring => c.slug.contains(id) || c.name.contains(id) || c.uuid.contains(id),
+        }
     }
+}
+
+// Shorter UUID prefixes are too likely to be a name
+const MIN_UUID_PREFIX: usize = 4;
 
+fn is_uuid_prefix(id: &str) -> bool {
+    id.len() >= MIN_UUID_PREFIX && id.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+}
 
-    pub fn find(&self, id: &str) -> Option<Check> {
-        let result = self.get(Some(id));
-        if result.is_err() {
-            eprintln!("Error: {:?}", result);
-            return None
+/// Resolves `id` trying, in order: exact UUID, unique UUID prefix, exact slug,
+/// exact name and unique substring of slug, name or UUID. The first step with
+/// any match decides, several matches make the ID ambiguous.
+pub fn resolve_id(id: &str, checks: &[Check]) -> Resolution {
+    let steps = [MatchKind::Uuid, MatchKind::UuidPrefix, MatchKind::Slug, MatchKind::Name, MatchKind::Substring];
+
+    for kind in steps {
+        let mut found: Vec<Check> = checks.iter().filter(|c| kind.matches(id, c)).cloned().collect();
+        match found.len() {
+            0 => continue,
+            1 => return Resolution::Found(Box::new(found.remove(0)), kind),
+            _ => return Resolution::Ambiguous(found)
         }
+    }
+
+    Resolution::NotFound
+}
 
-        let checks = result.unwrap();
-        if checks.is_empty() {
-            return None
+/// Sets short UUIDs like git abbreviates hashes: the first UUID segment,
+/// extended as long as another check shares it
+pub fn set_short_uuids(checks: &mut [Check]) {
+    let uuids: Vec<String> = checks.iter().map(|c| c.uuid.clone()).collect();
+
+    for (i, c) in checks.iter_mut().enumerate() {
+        c.set_short_uuid();
+        if c.uuid.is_empty() {
+            continue
         }
 
-        checks.first().cloned()
+        let common = uuids.iter().enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, other)| c.uuid.chars().zip(other.chars()).take_while(|(a, b)| a == b).count())
+            .max()
+            .unwrap_or(0);
+
+        let mut len = c.short_uuid.len().max(common + 1).min(c.uuid.len());
+        // do not end the short UUID with a hyphen
+        if c.uuid[..len].ends_with('-') {
+            len += 1;
+        }
+        c.short_uuid = c.uuid[..len.min(c.uuid.len())].to_string();
     }
 }
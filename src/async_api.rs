@@ -0,0 +1,158 @@
+use chrono::prelude::*;
+use reqwest::{Client, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use uuid::Uuid;
+use crate::api::*;
+use crate::error::{self, Error};
+
+/// Asynchronous counterpart of `ApiClient`, for use from async runtimes like tokio.
+/// Requests are not retried, `RetryPolicy::delay` tells when and after how long
+/// to retry them using the runtime's timer
+pub struct AsyncApiClient {
+    client: Client,
+    // pings are sent without the API key
+    ping_client: Client,
+    pub base_url: String,
+    pub ping_base_url: String
+}
+
+// Sends request, failures and error statuses are mapped to `Error`
+async fn send(request: RequestBuilder) -> Result<Response, Error> {
+    let response = request.send().await.map_err(Error::network)?;
+
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let retry_after = error::retry_after(response.headers());
+    let path = response.url().path().to_string();
+    let body = response.text().await.unwrap_or_default();
+    Err(Error::from_status(status, retry_after, path, body))
+}
+
+async fn json<T: DeserializeOwned>(request: RequestBuilder) -> Result<T, Error> {
+    send(request).await?.json().await.map_err(Error::decode)
+}
+
+async fn check(request: RequestBuilder) -> Result<Check, Error> {
+    let mut check: Check = json(request).await?;
+    check.set_short_uuid();
+    Ok(check)
+}
+
+impl AsyncApiClient {
+    /// Creates client using given API key (empty key for pings only) and URL of
+    /// the checks endpoint, healthchecks.io when `None`. Fails with `Error::Invalid`
+    /// for keys which can't be sent in a header, e.g. with a trailing newline
+    pub fn new(api_key: &str, base_url: Option<&str>) -> Result<AsyncApiClient, Error> {
+        let client = Client::builder()
+            .default_headers(api_headers(api_key)?)
+            .timeout(std::time::Duration::from_secs(30))
+            .build()
+            .map_err(Error::network)?;
+
+        let ping_client = Client::builder()
+            .timeout(std::time::Duration::from_secs(30))
+            .build()
+            .map_err(Error::network)?;
+
+        Ok(AsyncApiClient {
+            client,
+            ping_client,
+            base_url: base_url.unwrap_or(BASE_URL).to_string(),
+            ping_base_url: PING_BASE_URL.to_string()
+        })
+    }
+
+    /// Creates a check, or returns the existing one with the same name. Grace is in seconds
+    pub async fn add(&self, name: &str, schedule: &ScheduleKind, grace: u32, tags: Option<&str>) -> Result<Check, Error> {
+        let c = add_request(name, schedule, grace, tags)?;
+        check(self.client.post(&self.base_url).json(&c)).await
+    }
+
+    /// Changes check's fields given in `patch`
+    pub async fn update(&self, c: &Check, patch: CheckPatch) -> Result<Check, Error> {
+        let body = update_request(patch)?;
+        check(self.client.post(&c.update_url).json(&body)).await
+    }
+
+    /// Deletes a check, returning it as it was before
+    pub async fn delete(&self, c: &Check) -> Result<Check, Error> {
+        check(self.client.delete(format!("{}{}", self.base_url, c.uuid))).await
+    }
+
+    /// Sends a ping of given kind, with optional body and run ID pairing start and end of a job
+    pub async fn ping_with(&self, c: &Check, kind: PingKind, body: Option<&[u8]>, rid: Option<Uuid>) -> Result<(), Error> {
+        self.ping_url(&c.ping_url, kind, body, rid).await
+    }
+
+    /// Like `ping_with`, but pings `ping_url` directly, without looking up the check
+    pub async fn ping_url(&self, ping_url: &str, kind: PingKind, body: Option<&[u8]>, rid: Option<Uuid>) -> Result<(), Error> {
+        let url = ping_request_url(ping_url, kind, rid)?;
+
+        let request = match body {
+            Some(body) => self.ping_client.post(url).body(body.to_vec()),
+            None => self.ping_client.get(url)
+        };
+
+        send(request).await?;
+        Ok(())
+    }
+
+    /// Pauses monitoring of a check until its next ping
+    pub async fn pause(&self, c: &Check) -> Result<Check, Error> {
+        check(self.client.post(format!("{}{}/pause", self.base_url, c.uuid))).await
+    }
+
+    /// Resumes a paused check
+    pub async fn resume(&self, c: &Check) -> Result<Check, Error> {
+        check(self.client.post(format!("{}{}/resume", self.base_url, c.uuid))).await
+    }
+
+    /// Lists check's recent pings, newest first
+    pub async fn pings(&self, c: &Check) -> Result<Vec<Ping>, Error> {
+        let v: Value = json(self.client.get(format!("{}{}/pings/", self.base_url, c.uuid))).await?;
+        serde_json::from_value(v["pings"].clone()).map_err(Error::decode)
+    }
+
+    /// Lists check's status changes, optionally since given time
+    pub async fn flips(&self, c: &Check, since: Option<DateTime<Local>>) -> Result<Vec<Flip>, Error> {
+        json(self.client.get(flips_url(&self.base_url, c, since))).await
+    }
+
+    /// Lists all checks, or those whose slug, name or UUID contains `query`
+    pub async fn get(&self, query: Option<&str>) -> Result<Vec<Check>, Error> {
+        self.get_tagged(query, &[]).await
+    }
+
+    /// Like `get`, but lists only checks having all of the `tags`, filtered by the server
+    pub async fn get_tagged(&self, query: Option<&str>, tags: &[&str]) -> Result<Vec<Check>, Error> {
+        let params: Vec<(&str, &str)> = tags.iter().map(|t| ("tag", *t)).collect();
+
+        let v: Value = json(self.client.get(&self.base_url).query(&params)).await?;
+        parse_checks(v, query)
+    }
+
+    /// Fetches a single check by its UUID or, with read-only API key, by its unique key
+    pub async fn get_check(&self, id: &str) -> Result<Check, Error> {
+        check(self.client.get(format!("{}{}", self.base_url, id))).await
+            .map_err(|e| e.not_found(id))
+    }
+
+    /// Resolves `id` to a single check, see `resolve_id`
+    pub async fn resolve(&self, id: &str) -> Result<(Check, MatchKind), Error> {
+        if is_uuid(id) || is_unique_key(id) {
+            return Ok((self.get_check(id).await?, MatchKind::Uuid));
+        }
+
+        let checks = self.get(None).await?;
+        resolve_id(id, &checks).into_result(id)
+    }
+
+    /// Like `resolve`, but a missing check is `None` rather than an error
+    pub async fn find(&self, id: &str) -> Result<Option<Check>, Error> {
+        found(self.resolve(id).await)
+    }
+}
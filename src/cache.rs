@@ -0,0 +1,105 @@
+// Last listing of checks kept in ~/.cache/hchk, so that commands taking check IDs
+// don't download all checks each time. Entries are per profile, tied to the API URL
+// and key they were fetched with.
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Local, TimeDelta};
+use serde::{Deserialize, Serialize};
+use hchk::{Check, MatchKind, Resolution};
+use hchk::api::{resolve_id, set_short_uuids};
+use crate::config::{write_private, xdg_dir};
+
+/// $XDG_CACHE_HOME/hchk or ~/.cache/hchk
+pub fn cache_dir() -> Result<PathBuf, String> {
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    /// RFC 3339
+    fetched: String,
+    fingerprint: u64,
+    checks: Vec<Check>,
+}
+
+pub struct Cache {
+    /// None when caching is disabled
+    path: Option<PathBuf>,
+    ttl: TimeDelta,
+    // Hash of API URL and key, the cache of another project is never used
+    fingerprint: u64,
+}
+
+impl Cache {
+    pub fn new(path: PathBuf, ttl: TimeDelta, base_url: &str, api_key: &str) -> Cache {
+        let mut hasher = DefaultHasher::new();
+        (base_url, api_key).hash(&mut hasher);
+
+        Cache { path: Some(path), ttl, fingerprint: hasher.finish() }
+    }
+
+    pub fn disabled() -> Cache {
+        Cache { path: None, ttl: TimeDelta::zero(), fingerprint: 0 }
+    }
+
+    /// Cached checks, if fetched within TTL with the same URL and key
+    pub fn checks(&self) -> Option<Vec<Check>> {
+        let data = fs::read(self.path.as_ref()?).ok()?;
+        let cached: CacheFile = serde_json::from_slice(&data).ok()?;
+
+        let fetched = DateTime::parse_from_rfc3339(&cached.fetched).ok()?;
+        let age = Local::now().signed_duration_since(fetched);
+        if cached.fingerprint != self.fingerprint || age > self.ttl || age < TimeDelta::zero() {
+            return None;
+        }
+
+        let mut checks = cached.checks;
+        set_short_uuids(&mut checks);
+        Some(checks)
+    }
+
+    /// Check with given ID, only if it is the exact UUID, slug or name of exactly
+    /// one cached check. Prefixes and substrings are left to the API, a check added
+    /// since the listing was cached may match them better or make them ambiguous
+    pub fn resolve(&self, id: &str) -> Option<(Check, MatchKind)> {
+        match resolve_id(id, &self.checks()?) {
+            Resolution::Found(check, kind @ (MatchKind::Uuid | MatchKind::Slug | MatchKind::Name)) => {
+                Some((*check, kind))
+            }
+            _ => None
+        }
+    }
+
+    /// Saves listing of all checks, failures are not fatal and only reported
+    pub fn store(&self, checks: &[Check]) {
+        let Some(path) = &self.path else {
+            return
+        };
+
+        let cached = CacheFile {
+            fetched: Local::now().to_rfc3339(),
+            fingerprint: self.fingerprint,
+            checks: checks.to_vec(),
+        };
+        if let Err(e) = write(path, &cached) {
+            eprintln!("hchk: failed to cache checks in {}: {}", path.display(), e);
+        }
+    }
+
+    /// Drops the cache, after changes to checks
+    pub fn invalidate(&self) {
+        if let Some(path) = &self.path {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+fn write(path: &Path, cached: &CacheFile) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    write_private(path, &serde_json::to_vec(cached)?)?;
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PROFILE: &str = "default";
+const PROFILE_ENV: &str = "HCHK_PROFILE";
+
+/// hchk directory under $`var` or, when it is not set, under ~/`fallback`,
+/// like $XDG_CONFIG_HOME/hchk or ~/.config/hchk
+pub fn xdg_dir(var: &str, fallback: &str) -> Result<PathBuf, String> {
+    let dir = match env::var(var) {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            let home = env::var("HOME").map_err(|_| "empty HOME environment variable")?;
+            PathBuf::from(home).join(fallback)
+        }
+    };
+
+    Ok(dir.join("hchk"))
+}
+
+/// Writes file readable only by its owner, for API keys and ping URLs. On Unix
+/// it is created with 0o600 permissions, and existing files get them before
+/// anything is written
+pub fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(contents)
+}
+
+/// Settings of a single healthchecks project
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Profile {
+    pub api_key: Option<String>,
+    pub read_only_key: Option<String>,
+    pub ping_key: Option<String>,
+    /// API base URL, e.g. https://hc.example.com/api/v3/
+    pub api_url: Option<String>,
+    /// Ping base URL, e.g. https://hc.example.com/ping/
+    pub ping_url: Option<String>,
+    /// Retries of failed pings and read-only requests
+    pub retries: Option<u32>,
+    /// Age of spooled pings to discard rather than deliver, e.g. 12h
+    pub spool_max_age: Option<String>,
+    /// How long cached listing of checks is used to look up check IDs, e.g. 10m
+    pub cache_ttl: Option<String>,
+}
+
+impl Profile {
+    /// URL of the checks endpoint, as expected by `ApiClient::new`
+    pub fn checks_url(&self) -> Option<String> {
+        self.api_url.as_ref().map(|url| format!("{}/checks/", url.trim_end_matches('/')))
+    }
+
+    pub fn ping_base_url(&self) -> Option<String> {
+        self.ping_url.as_ref().map(|url| format!("{}/", url.trim_end_matches('/')))
+    }
+}
+
+/// Contents of ~/.config/hchk/config.toml, profiles are kept in [profiles.NAME] tables
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Config {
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl Config {
+    pub fn path() -> Result<PathBuf, String> {
+        Ok(xdg_dir("XDG_CONFIG_HOME", ".config")?.join("config.toml"))
+    }
+
+    pub fn parse(s: &str) -> Result<Config, String> {
+        toml::from_str(s).map_err(|e| format!("Invalid config: {}", e))
+    }
+
+    /// Loads the config file, missing file gives an empty config
+    pub fn load() -> Result<Config, Box<dyn std::error::Error>> {
+        let path = Config::path()?;
+        if !path.is_file() {
+            return Ok(Config::default());
+        }
+
+        let contents = fs::read_to_string(&path)?;
+        Config::parse(&contents)
+            .map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = Config::path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        write_private(&path, toml::to_string(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn profile(&self, name: &str) -> Profile {
+        self.profiles.get(name).cloned().unwrap_or_default()
+    }
+
+    pub fn profile_mut(&mut self, name: &str) -> &mut Profile {
+        self.profiles.entry(name.to_string()).or_default()
+    }
+}
+
+/// Name of the active profile: --profile option, HCHK_PROFILE or "default"
+pub fn active_profile(name: Option<&str>) -> String {
+    if let Some(name) = name {
+        return name.to_string();
+    }
+
+    match env::var(PROFILE_ENV) {
+        Ok(name) if !name.is_empty() => name,
+        _ => DEFAULT_PROFILE.to_string()
+    }
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+use reqwest::StatusCode;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::blocking::Response;
+use crate::api::Check;
+
+/// Errors returned by `ApiClient`, more variants may be added
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Missing, invalid or insufficient API key (HTTP 401 and 403)
+    Unauthorized,
+    /// Check (or ping) with given ID does not exist
+    NotFound(String),
+    /// ID matches more than one check
+    Ambiguous { id: String, candidates: Vec<Check> },
+    /// Too many requests (HTTP 429), `retry_after` is in seconds
+    RateLimited { retry_after: Option<u64> },
+    /// Request conflicts with an existing check (HTTP 409)
+    Conflict(String),
+    /// Any other error status, with the response body
+    Server(u16, String),
+    /// Connection failure or timeout
+    Network(NetworkError),
+    /// Unexpected response contents
+    Decode(String),
+    /// Invalid argument, the request was not sent
+    Invalid(String),
+}
+
+/// Failure to connect or to get a response, details are in the message and `source`
+#[derive(Debug)]
+pub struct NetworkError(reqwest::Error);
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for NetworkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        std::error::Error::source(&self.0)
+    }
+}
+
+impl Error {
+    pub(crate) fn network(e: reqwest::Error) -> Error {
+        Error::Network(NetworkError(e))
+    }
+
+    pub(crate) fn decode<E: fmt::Display>(e: E) -> Error {
+        Error::Decode(e.to_string())
+    }
+
+    /// True for failures likely to go away: network errors, rate limiting and
+    /// server errors (HTTP 5xx)
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Network(_) | Error::RateLimited { .. } => true,
+            Error::Server(status, _) => *status >= 500,
+            _ => false
+        }
+    }
+
+    // Reports missing check under its ID rather than the request path
+    pub(crate) fn not_found(self, id: &str) -> Error {
+        match self {
+            Error::NotFound(_) => Error::NotFound(id.to_string()),
+            e => e
+        }
+    }
+
+    /// Maps error status of `response` to `Error`, successful responses are passed through
+    pub(crate) fn check_status(response: Response) -> Result<Response, Error> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let retry_after = retry_after(response.headers());
+        let path = response.url().path().to_string();
+        let body = response.text().unwrap_or_default();
+        Err(Error::from_status(status, retry_after, path, body))
+    }
+
+    pub(crate) fn from_status(status: StatusCode, retry_after: Option<u64>, path: String, body: String) -> Error {
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Error::Unauthorized,
+            StatusCode::NOT_FOUND => Error::NotFound(path),
+            StatusCode::CONFLICT => Error::Conflict(body),
+            StatusCode::TOO_MANY_REQUESTS => Error::RateLimited { retry_after },
+            _ => Error::Server(status.as_u16(), body)
+        }
+    }
+}
+
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<u64> {
+    headers.get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Unauthorized => write!(f, "API error: unauthorized, check the API key"),
+            Error::NotFound(id) => write!(f, "{}: not found", id),
+            Error::Ambiguous { id, candidates } => {
+                write!(f, "{}: ambiguous ID, it matches:", id)?;
+                for c in candidates {
+                    write!(f, "\n  {} {}", c.short_uuid, c.name)?;
+                }
+                Ok(())
+            }
+            Error::RateLimited { retry_after: Some(secs) } => write!(f, "API error: rate limited, retry after {}s", secs),
+            Error::RateLimited { retry_after: None } => write!(f, "API error: rate limited"),
+            Error::Conflict(body) => write!(f, "API error: conflict: {}", body.trim()),
+            Error::Server(status, body) if body.trim().is_empty() => write!(f, "API error: HTTP {}", status),
+            Error::Server(status, body) => write!(f, "API error: HTTP {}: {}", status, body.trim()),
+            Error::Network(e) => write!(f, "request failed: {}", e),
+            Error::Decode(msg) => write!(f, "Failed to parse response: {}", msg),
+            Error::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Network(e) => Some(e),
+            _ => None
+        }
+    }
+}
+
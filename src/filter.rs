@@ -0,0 +1,330 @@
+// Query language selecting checks, e.g.
+//
+//   status:down,grace tag:prod name~^backup- schedule:"0 * * * *" last_ping>2h paused:true
+//
+// Terms next to each other are ANDed, OR, NOT (or a leading "-") and parentheses
+// combine them. A bare word matches a substring of check's slug, name or UUID.
+use chrono::{Local, TimeDelta};
+use regex::Regex;
+use hchk::{Check, Error};
+
+#[derive(Debug)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Word(String)
+}
+
+// Split query into words, quotes group characters (including spaces and
+// parentheses) into a single word
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => { chars.next(); }
+            '(' => { chars.next(); tokens.push(Token::LParen); }
+            ')' => { chars.next(); tokens.push(Token::RParen); }
+            _ => {
+                let mut word = String::new();
+                let mut quoted = false;
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break
+                    }
+                    chars.next();
+                    if c != '"' {
+                        word.push(c);
+                        continue
+                    }
+
+                    quoted = true;
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some(c) => word.push(c),
+                            None => return Err(format!("Unterminated quote in query: {}", s)),
+                        }
+                    }
+                }
+
+                let token = match word.as_str() {
+                    "AND" if !quoted => Token::And,
+                    "OR" if !quoted => Token::Or,
+                    "NOT" if !quoted => Token::Not,
+                    _ => Token::Word(word)
+                };
+                tokens.push(token);
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug)]
+enum Cmp {
+    Less,
+    Greater
+}
+
+#[derive(Debug)]
+enum Term {
+    Substring(String),
+    Equals(String, Vec<String>),
+    Tag(Vec<String>),
+    Paused(bool),
+    Matches(String, Regex),
+    Age(String, Cmp, TimeDelta),
+    Count(Cmp, u64)
+}
+
+#[derive(Debug)]
+enum Expr {
+    Term(Term),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>)
+}
+
+const TEXT_FIELDS: [&str; 9] = ["name", "slug", "uuid", "id", "desc", "tags", "status", "schedule", "tz"];
+const TIME_FIELDS: [&str; 4] = ["last_ping", "next_ping", "grace", "timeout"];
+
+fn text_field(check: &Check, field: &str) -> String {
+    match field {
+        "name" => check.name.clone(),
+        "slug" => check.slug.clone(),
+        "uuid" => check.uuid.clone(),
+        "id" => check.short_uuid.clone(),
+        "desc" => check.desc.clone(),
+        "tags" => check.tags.clone(),
+        "status" => check.status.clone(),
+        "schedule" => check.schedule.expr().unwrap_or_default().to_string(),
+        "tz" => check.schedule.tz().unwrap_or_default().to_string(),
+        _ => String::new()
+    }
+}
+
+fn parse_term(word: &str) -> Result<Term, String> {
+    let Some(pos) = word.find([':', '~', '<', '>']) else {
+        return Ok(Term::Substring(word.to_string()));
+    };
+
+    let (field, rest) = word.split_at(pos);
+    let (op, value) = rest.split_at(1);
+    if value.is_empty() {
+        return Err(format!("Missing value in query term: {}", word));
+    }
+
+    let cmp = match op {
+        "<" => Some(Cmp::Less),
+        ">" => Some(Cmp::Greater),
+        _ => None
+    };
+
+    match (field, op, cmp) {
+        ("tag" | "tags", ":", _) => Ok(Term::Tag(value.split(',').map(String::from).collect())),
+        ("paused", ":", _) => match value {
+            "true" | "yes" => Ok(Term::Paused(true)),
+            "false" | "no" => Ok(Term::Paused(false)),
+            _ => Err(format!("paused: expects true or false, got: {}", value))
+        },
+        (f, ":", _) if TEXT_FIELDS.contains(&f) => {
+            Ok(Term::Equals(f.to_string(), value.split(',').map(String::from).collect()))
+        }
+        (f, "~", _) if TEXT_FIELDS.contains(&f) => {
+            let re = Regex::new(value).map_err(|e| format!("{}: {}", word, e))?;
+            Ok(Term::Matches(f.to_string(), re))
+        }
+        ("n_pings", _, Some(cmp)) => {
+            let n = value.parse().map_err(|_| format!("{}: expects a number", word))?;
+            Ok(Term::Count(cmp, n))
+        }
+        (f, _, Some(cmp)) if TIME_FIELDS.contains(&f) => {
+            Ok(Term::Age(f.to_string(), cmp, crate::parse_duration(value).map_err(|e| e.to_string())?))
+        }
+        _ => Err(format!("Invalid query term: {}", word))
+    }
+}
+
+impl Term {
+    fn matches(&self, check: &Check) -> bool {
+        match self {
+            Term::Substring(s) => check.slug.contains(s) || check.name.contains(s) || check.uuid.contains(s),
+            Term::Equals(field, values) if field == "uuid" || field == "id" => {
+                values.iter().any(|v| check.uuid.starts_with(v.as_str()))
+            }
+            Term::Equals(field, values) => {
+                let v = text_field(check, field);
+                values.contains(&v)
+            }
+            Term::Tag(tags) => tags.iter().any(|t| check.has_tag(t)),
+            Term::Paused(paused) => (check.status == "paused") == *paused,
+            Term::Matches(field, re) => re.is_match(&text_field(check, field)),
+            Term::Count(cmp, n) => compare(cmp, check.n_pings as i64, *n as i64),
+            Term::Age(field, cmp, d) => {
+                let secs = match field.as_str() {
+                    // time since last ping, checks never pinged are infinitely old
+                    "last_ping" if check.last_ping.is_none() => i64::MAX,
+                    "last_ping" => (Local::now() - check.last_ping_at()).num_seconds(),
+                    // time until next expected ping
+                    "next_ping" if check.next_ping.is_none() => return false,
+                    "next_ping" => (check.next_ping_at() - Local::now()).num_seconds(),
+                    "grace" => check.grace as i64,
+                    "timeout" => match check.schedule.timeout() {
+                        Some(t) => t as i64,
+                        None => return false
+                    },
+                    _ => return false
+                };
+                compare(cmp, secs, d.num_seconds())
+            }
+        }
+    }
+}
+
+fn compare(cmp: &Cmp, a: i64, b: i64) -> bool {
+    match cmp {
+        Cmp::Less => a < b,
+        Cmp::Greater => a > b,
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        self.pos += 1;
+        self.tokens.get(self.pos - 1)
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut left = self.and()?;
+        while let Some(Token::Or) = self.peek() {
+            self.next();
+            let right = self.and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => { self.next(); }
+                Some(Token::Word(_) | Token::Not | Token::LParen) => {}
+                _ => break
+            }
+            let right = self.unary()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::LParen) => {
+                let e = self.or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(e),
+                    _ => Err("Missing ')' in query".to_string())
+                }
+            }
+            Some(Token::Word(w)) if w.len() > 1 && w.starts_with('-') => {
+                Ok(Expr::Not(Box::new(Expr::Term(parse_term(&w[1..])?))))
+            }
+            Some(Token::Word(w)) => Ok(Expr::Term(parse_term(w)?)),
+            Some(t) => Err(format!("Unexpected {:?} in query", t)),
+            None => Err("Unexpected end of query".to_string())
+        }
+    }
+}
+
+impl Expr {
+    fn matches(&self, check: &Check) -> bool {
+        match self {
+            Expr::Term(t) => t.matches(check),
+            Expr::Not(e) => !e.matches(check),
+            Expr::And(a, b) => a.matches(check) && b.matches(check),
+            Expr::Or(a, b) => a.matches(check) || b.matches(check),
+        }
+    }
+
+    // Anything beyond a single bare word
+    fn is_selector(&self) -> bool {
+        !matches!(self, Expr::Term(Term::Substring(_)))
+    }
+}
+
+/// Parsed query, an empty query matches all checks
+#[derive(Debug)]
+pub struct Filter {
+    expr: Option<Expr>
+}
+
+// Words after the first are plain text, not operators nor terms of their own
+fn is_single_term(arg: &str) -> bool {
+    let mut words = arg.split_whitespace();
+    let first = words.next().unwrap_or_default();
+    if first.starts_with(['-', '(']) || ["AND", "OR", "NOT"].contains(&first) {
+        return false;
+    }
+
+    words.all(|w| {
+        !["AND", "OR", "NOT"].contains(&w) && !w.starts_with(['-', '(']) && !w.ends_with(')') &&
+            !w.contains([':', '~', '<', '>', '"'])
+    })
+}
+
+impl Filter {
+    pub fn parse(s: &str) -> Result<Filter, Error> {
+        let mut parser = Parser { tokens: tokenize(s).map_err(Error::Invalid)?, pos: 0 };
+        if parser.peek().is_none() {
+            return Ok(Filter { expr: None });
+        }
+
+        let expr = parser.or().map_err(Error::Invalid)?;
+        if let Some(t) = parser.peek() {
+            return Err(Error::Invalid(format!("Unexpected {:?} in query", t)));
+        }
+
+        Ok(Filter { expr: Some(expr) })
+    }
+
+    /// Builds query from command line arguments. An argument holding a whole query,
+    /// like 'tag:db OR tag:www', is grouped in parentheses. One holding a single term
+    /// whose value has spaces, like schedule:"0 * * * *" with quotes eaten by the
+    /// shell, is quoted back
+    pub fn from_args(args: &[String]) -> Result<Filter, Error> {
+        let query: Vec<String> = args.iter()
+            .map(|a| match a.split_whitespace().count() {
+                0 | 1 => a.clone(),
+                _ if is_single_term(a) => format!("\"{}\"", a),
+                _ => format!("({})", a)
+            })
+            .collect();
+        Filter::parse(&query.join(" "))
+    }
+
+    pub fn matches(&self, check: &Check) -> bool {
+        self.expr.as_ref().is_none_or(|e| e.matches(check))
+    }
+
+    /// True if the query is more than a plain check ID
+    pub fn is_selector(&self) -> bool {
+        self.expr.as_ref().is_some_and(|e| e.is_selector())
+    }
+}
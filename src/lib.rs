@@ -0,0 +1,30 @@
+//! Client for the [healthchecks.io](https://healthchecks.io) management and ping APIs,
+//! also working with self-hosted instances.
+//!
+//! ```no_run
+//! use hchk::{ApiClient, PingKind};
+//!
+//! let client = ApiClient::new("API_KEY", None)?;
+//! if let Some(check) = client.find("nightly-backup")? {
+//!     client.ping_with(&check, PingKind::Start, None, None)?;
+//! }
+//! # Ok::<(), hchk::Error>(())
+//! ```
+//!
+//! The `hchk` command line tool is built with the default `cli` feature, library
+//! users can turn it off with `default-features = false`. The `async` feature adds
+//! `AsyncApiClient` with the same operations, sharing models and errors.
+
+pub mod api;
+#[cfg(feature = "async")]
+pub mod async_api;
+pub mod error;
+pub mod oncalendar;
+pub mod retry;
+
+pub use api::{ApiClient, Check, CheckPatch, Flip, MatchKind, Ping, PingKind, Resolution, ScheduleKind, Uptime, PING_BASE_URL};
+pub use error::Error;
+pub use oncalendar::OnCalendar;
+pub use retry::RetryPolicy;
+#[cfg(feature = "async")]
+pub use async_api::AsyncApiClient;
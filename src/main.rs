@@ -4,33 +4,76 @@ use std::fs::File;
 use std::path::Path;
 use std::io::prelude::*;
 use std::io::{self, IsTerminal};
+use std::collections::{HashSet, VecDeque};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
 use clap::{Parser, Subcommand};
 use colored::*;
+use chrono::{DateTime, Local, TimeDelta, Utc};
+use serde::Serialize;
+use uuid::Uuid;
 
-mod api;
-use crate::api::ApiClient;
+mod config;
+mod filter;
+mod cache;
+mod output;
+mod spool;
+use hchk::{ApiClient, Check, CheckPatch, Error, MatchKind, OnCalendar, PingKind, RetryPolicy, ScheduleKind, Uptime};
+use hchk::api::{is_unique_key, is_uuid, resolve_id};
+use hchk::oncalendar::parse_tz;
+use crate::cache::{Cache, cache_dir};
+use crate::config::{Config, Profile, DEFAULT_PROFILE};
+use crate::filter::Filter;
+use crate::output::{OutputFormat, print_checks, DEFAULT_COLUMNS};
+use crate::spool::{Spool, SpooledPing};
 
 #[cfg(test)]
 mod tests;
 
 /// healthchecks.io command line client
 #[derive(Parser, Debug)]
-#[command(name = "hchk", version)]
+#[command(name = "hchk", version, after_help = EXIT_CODES_HELP)]
 struct Cli {
     /// Be verbose
     #[arg(short = 'v', action = clap::ArgAction::Count)]
     verbose: u8,
 
+    /// Config profile to use, defaults to $HCHK_PROFILE or "default"
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// Retries of failed pings and read-only requests, defaults to profile's retries or 3
+    #[arg(long, global = true, value_name = "N")]
+    retries: Option<u32>,
+
+    /// Don't use cached listing of checks to look up check IDs
+    #[arg(long, global = true)]
+    no_cache: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
-    /// Save API key to $HOME/.hchk
+    /// Save API keys and URLs to the active profile in ~/.config/hchk/config.toml
     Setkey {
         /// API key
+        #[arg(required_unless_present_any = ["ping_key", "read_only_key", "api_url", "ping_url"])]
         key: Option<String>,
+        /// Project's ping key, used to ping checks by slug
+        #[arg(long)]
+        ping_key: Option<String>,
+        /// Read-only API key, used by ls and report when there is no API key
+        #[arg(long)]
+        read_only_key: Option<String>,
+        /// API base URL of self-hosted instance, e.g. https://hc.example.com/api/v3/
+        #[arg(long)]
+        api_url: Option<String>,
+        /// Ping base URL of self-hosted instance, e.g. https://hc.example.com/ping/
+        #[arg(long)]
+        ping_url: Option<String>,
     },
     /// List checks
     Ls {
@@ -43,36 +86,210 @@ enum Commands {
         /// List 'down' only checks
         #[arg(short = 'd')]
         down: bool,
-        /// Filter by name/id
-        query: Option<String>,
+        /// Filter by name/id or query, e.g. status:down tag:prod name~^backup- last_ping>2h
+        query: Vec<String>,
+        /// List only checks with this tag, repeat to require several tags
+        #[arg(short = 't', long = "tag")]
+        tags: Vec<String>,
+        /// Comma separated columns to show, e.g. status,name,tags,schedule,next_ping,grace,n_pings,ping_url
+        #[arg(long, value_delimiter = ',', conflicts_with = "format")]
+        columns: Vec<String>,
+        /// Print each check using template with check's fields, e.g. '{name}\t{ping_url}'
+        #[arg(long)]
+        format: Option<String>,
+        /// Output format
+        #[arg(short = 'o', long, value_enum, default_value_t)]
+        output: OutputFormat,
+    },
+    /// Show check's details
+    Show {
+        /// Check's ID
+        id: String,
+        /// Output format
+        #[arg(short = 'o', long, value_enum, default_value_t)]
+        output: OutputFormat,
     },
     /// Add check
     Add {
         /// Name
         name: String,
+        /// Schedule in cron or OnCalendar format
+        #[arg(required_unless_present_any = ["every", "oncalendar"], conflicts_with_all = ["every", "oncalendar"])]
+        schedule: Option<String>,
+        /// Grace period, e.g. 15m or 2h, a bare number is hours
+        grace: Option<String>,
+        /// Timezone
+        tz: Option<String>,
+        /// Tags
+        tags: Option<String>,
+        /// Expect a ping every period, e.g. 1h or 1d, instead of following a cron schedule
+        #[arg(long, value_name = "PERIOD", conflicts_with = "oncalendar")]
+        every: Option<String>,
+        /// Schedule as systemd OnCalendar expression, e.g. "Mon..Fri 02:00"
+        #[arg(long, value_name = "EXPR")]
+        oncalendar: Option<String>,
+        /// Time zone of the schedule
+        #[arg(long = "tz", value_name = "TZ", conflicts_with = "tz")]
+        tz_opt: Option<String>,
+        /// Grace period, e.g. 15m or 2h, a bare number is hours
+        #[arg(long = "grace", value_name = "GRACE", conflicts_with = "grace")]
+        grace_opt: Option<String>,
+        /// Tags
+        #[arg(long = "tags", value_name = "TAGS", conflicts_with = "tags")]
+        tags_opt: Option<String>,
+        /// Output format
+        #[arg(short = 'o', long, value_enum, default_value_t)]
+        output: OutputFormat,
+    },
+    /// Edit check
+    Edit {
+        /// Check's ID to edit
+        id: String,
+        /// New name
+        #[arg(long)]
+        name: Option<String>,
+        /// Description
+        #[arg(long)]
+        desc: Option<String>,
         /// Schedule in cron format
-        schedule: String,
-        /// Grace in hours
+        #[arg(long, conflicts_with_all = ["every", "oncalendar"])]
+        schedule: Option<String>,
+        /// Expect a ping every period, e.g. 1h or 1d, instead of following a schedule
+        #[arg(long, value_name = "PERIOD", conflicts_with = "oncalendar")]
+        every: Option<String>,
+        /// Schedule as systemd OnCalendar expression, e.g. "Mon..Fri 02:00"
+        #[arg(long, value_name = "EXPR")]
+        oncalendar: Option<String>,
+        /// Grace period, e.g. 15m or 2h, a bare number is hours
+        #[arg(long)]
         grace: Option<String>,
         /// Timezone
+        #[arg(long)]
         tz: Option<String>,
         /// Tags
+        #[arg(long)]
         tags: Option<String>,
+        /// Output format
+        #[arg(short = 'o', long, value_enum, default_value_t)]
+        output: OutputFormat,
     },
-    /// Delete check
+    /// Delete checks
     Del {
-        /// Check's ID to delete
-        id: String,
+        /// IDs of checks to delete, or a query
+        #[arg(required = true)]
+        ids: Vec<String>,
+        /// Delete all checks matched by query without asking
+        #[arg(short = 'y', long)]
+        yes: bool,
+        /// Output format
+        #[arg(short = 'o', long, value_enum, default_value_t)]
+        output: OutputFormat,
     },
-    /// Pause check
+    /// Pause checks
     Pause {
-        /// Check's ID to pause
+        /// IDs of checks to pause, or a query
+        #[arg(required = true)]
+        ids: Vec<String>,
+        /// Output format
+        #[arg(short = 'o', long, value_enum, default_value_t)]
+        output: OutputFormat,
+    },
+    /// Resume paused checks
+    Resume {
+        /// IDs of checks to resume, or a query
+        #[arg(required_unless_present = "tag")]
+        ids: Vec<String>,
+        /// Resume all checks with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Output format
+        #[arg(short = 'o', long, value_enum, default_value_t)]
+        output: OutputFormat,
+    },
+    /// Show check's recent pings
+    Log {
+        /// Check's ID
         id: String,
+        /// Show at most this many pings
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+        /// Show only pings of this type
+        #[arg(short = 't', long = "type", value_parser = ["success", "fail", "start", "log"])]
+        kind: Option<String>,
+        /// Show pings newer than this (duration like 2h, 7d or a RFC 3339 date)
+        #[arg(long)]
+        since: Option<String>,
+        /// Show pings older than this (duration like 2h, 7d or a RFC 3339 date)
+        #[arg(long)]
+        until: Option<String>,
+        /// Print body of ping number N, or of the latest failure if N is omitted
+        #[arg(long, value_name = "N")]
+        body: Option<Option<u32>>,
+    },
+    /// Report checks' uptime
+    Report {
+        /// Report period (duration like 30d or 12h)
+        #[arg(long, default_value = "30d")]
+        since: String,
+        /// Report only checks with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Report only checks matching query
+        query: Vec<String>,
+        /// Output report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Run command and report its exit status to check
+    Run {
+        /// Check's ID, UUID or ping URL, UUIDs and URLs are pinged without API key
+        id: String,
+        /// Send last N kB of command's output with the ping
+        #[arg(long, value_name = "N", default_value_t = 10)]
+        capture_kb: usize,
+        /// Command to run
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
     },
     /// Ping check
     Ping {
-        /// Check's ID to ping
-        id: String,
+        /// Check's ID, UUID or ping URL to ping, UUIDs and URLs are pinged without API key
+        #[arg(required_unless_present = "slug", conflicts_with = "slug")]
+        id: Option<String>,
+        /// Project's ping key, used with --slug instead of the one saved with setkey
+        #[arg(long, requires = "slug")]
+        ping_key: Option<String>,
+        /// Ping check by its slug, without API key
+        #[arg(long)]
+        slug: Option<String>,
+        /// Create check with given slug if it does not exist
+        #[arg(long, requires = "slug")]
+        create: bool,
+        /// Signal that the job has started
+        #[arg(long, group = "kind")]
+        start: bool,
+        /// Signal that the job has failed
+        #[arg(long, group = "kind")]
+        fail: bool,
+        /// Send a log message without changing check's status
+        #[arg(long, group = "kind")]
+        log: bool,
+        /// Report job's exit status, non-zero means failure
+        #[arg(long, value_name = "N", group = "kind")]
+        exit_code: Option<u8>,
+        /// Send contents of file as ping body, "-" reads stdin
+        #[arg(long, value_name = "FILE")]
+        body_file: Option<String>,
+        /// Run ID pairing the start ping with the ping ending the job
+        #[arg(long)]
+        rid: Option<Uuid>,
+    },
+    /// Deliver pings spooled while the ping endpoint was unreachable
+    Flush {
+        /// Discard spooled pings older than this, e.g. 12h, defaults to profile's
+        /// spool_max_age or 1d
+        #[arg(long)]
+        max_age: Option<String>,
     },
 }
 
@@ -86,20 +303,59 @@ fn colored_status(status: &str) -> ColoredString {
     }
 }
 
-struct LsFlags {
+struct LsFlags<'a> {
     up: bool,
     down: bool,
-    long: bool
+    long: bool,
+    output: OutputFormat,
+    columns: &'a [String],
+    format: Option<&'a str>,
+    tags: &'a [String]
 }
 
-fn cmd_list_checks(client: &ApiClient, flags: &LsFlags, query: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
-    let result = client.get(query)?;
+// Checks having all of the `tags`. Short IDs come from the cached listing of all
+// checks, when it is fresh, to be unique among them as they are when listed
+// without tags. Otherwise they are unique among the tagged checks only, the full
+// listing is not downloaded just for them
+fn list_checks(client: &ApiClient, cache: &Cache, tags: &[&str]) -> Result<Vec<Check>, Error> {
+    if tags.is_empty() {
+        let checks = client.get(None)?;
+        cache.store(&checks);
+        return Ok(checks);
+    }
+
+    let mut checks = client.get_tagged(None, tags)?;
+    let Some(all) = cache.checks() else {
+        return Ok(checks);
+    };
+    for c in &mut checks {
+        if let Some(listed) = all.iter().find(|a| a.id() == c.id()) {
+            c.short_uuid = listed.short_uuid.clone();
+        }
+    }
+    Ok(checks)
+}
 
-    let mut checks = result;
+fn cmd_list_checks(client: &ApiClient, cache: &Cache, flags: &LsFlags, query: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let filter = Filter::from_args(query)?;
+    let tags: Vec<&str> = flags.tags.iter().map(String::as_str).collect();
+    let mut checks = list_checks(client, cache, &tags)?;
+    checks.retain(|c| filter.matches(c));
 
     checks.sort_by(|a, b| a.name.cmp(&b.name));
     if flags.up || flags.down {
-        checks = checks.into_iter().filter(|c| (flags.down && c.status == "down") || (flags.up && c.status == "up")).collect();
+        checks.retain(|c| (flags.down && c.status == "down") || (flags.up && c.status == "up"));
+    }
+
+    if flags.output != OutputFormat::Table {
+        return print_checks(&checks, flags.output);
+    }
+
+    if let Some(template) = flags.format {
+        for c in &checks {
+            println!("{}", output::format_check(c, template)?);
+        }
+        return Ok(());
     }
 
     let tty = io::stdout().is_terminal();
@@ -107,154 +363,1004 @@ fn cmd_list_checks(client: &ApiClient, flags: &LsFlags, query: Option<&str>) ->
         println!("total {:?}", checks.len());
     }
 
-    for c in checks {
-        if flags.long {
+    if flags.long {
+        for c in checks {
             println!("{}", serde_json::to_string_pretty(&c)?);
+        }
+        return Ok(());
+    }
+
+    let columns: Vec<String> = if flags.columns.is_empty() {
+        DEFAULT_COLUMNS.iter().map(|c| c.to_string()).collect()
+    } else {
+        flags.columns.to_vec()
+    };
+    output::validate_columns(&columns)?;
+
+    let rows: Vec<Vec<String>> = checks.iter()
+        .map(|c| columns.iter().map(|col| output::cell(c, col)).collect())
+        .collect();
+
+    // Fit the terminal, output to pipes is never truncated
+    let max_width = if tty { output::terminal_width() } else { None };
+    let widths = output::column_widths(&rows, max_width);
+
+    for row in rows {
+        let cells: Vec<String> = row.iter().zip(&columns).zip(&widths)
+            .map(|((value, col), width)| {
+                let value = output::fit(value, *width);
+                if col == "status" && tty {
+                    return colored_status(value.trim_end()).to_string() + &value[value.trim_end().len()..];
+                }
+                value
+            })
+            .collect();
+
+        println!("{}", cells.join(" ").trim_end());
+    }
+
+    Ok(())
+}
+
+// The cache only maps `id` to a UUID, the check itself is fetched as it is now.
+// Checks deleted or renamed since they were cached are left to the full lookup,
+// another check may have taken the name meanwhile
+fn cached_check(client: &ApiClient, cache: &Cache, id: &str) -> Result<Option<(Check, MatchKind)>, Error> {
+    let Some((cached, kind)) = cache.resolve(id) else {
+        return Ok(None);
+    };
+
+    match client.get_check(cached.id()) {
+        Ok(mut check) if kind.matches(id, &check) => {
+            check.short_uuid = cached.short_uuid;
+            Ok(Some((check, kind)))
+        }
+        Ok(_) | Err(Error::NotFound(_)) => {
+            cache.invalidate();
+            Ok(None)
+        }
+        Err(e) => Err(e)
+    }
+}
+
+fn resolve_check(client: &ApiClient, cache: &Cache, id: &str) -> Result<Check, Box<dyn std::error::Error>> {
+    let (check, kind) = match cached_check(client, cache, id)? {
+        Some(found) => found,
+        None if is_uuid(id) || is_unique_key(id) => client.resolve(id)?,
+        None => {
+            let checks = client.get(None)?;
+            cache.store(&checks);
+            resolve_id(id, &checks).into_result(id)?
+        }
+    };
+
+    // Make partial matches visible, "backup" resolving to "backup-db" should not go unnoticed
+    if kind == MatchKind::Substring {
+        eprintln!("{}: using check {} ({})", id, check.name, check.short_uuid);
+    }
+
+    Ok(check)
+}
+
+// Checks given by IDs, or all checks matching a query when arguments are more than plain IDs
+fn select_checks(client: &ApiClient, cache: &Cache, args: &[String]) -> Result<Vec<Check>, Box<dyn std::error::Error>> {
+    Ok(select_checks_by(client, cache, args)?.0)
+}
+
+// Like `select_checks`, also telling whether the arguments were taken as a query
+fn select_checks_by(client: &ApiClient, cache: &Cache, args: &[String]) -> Result<(Vec<Check>, bool), Box<dyn std::error::Error>> {
+    let resolve_all = || args.iter()
+        .map(|id| resolve_check(client, cache, id))
+        .collect::<Result<Vec<Check>, _>>()
+        .map(|checks| (checks, false));
+
+    let plain_ids = args.iter().all(|a| Filter::from_args(std::slice::from_ref(a)).is_ok_and(|f| !f.is_selector()));
+    if plain_ids {
+        return resolve_all();
+    }
+
+    // Names like "prod: backup" are not valid queries but still check IDs
+    let filter = match Filter::from_args(args) {
+        Ok(filter) => filter,
+        Err(e) => return resolve_all().map_err(|err| match err.downcast_ref::<Error>() {
+            Some(Error::NotFound(_)) => e.into(),
+            _ => err
+        })
+    };
+    let mut checks = client.get(None)?;
+    cache.store(&checks);
+    checks.retain(|c| filter.matches(c));
+    checks.sort_by(|a, b| a.name.cmp(&b.name));
+
+    if checks.is_empty() {
+        return Err(Error::NotFound(args.join(" ")).into());
+    }
+    Ok((checks, true))
+}
+
+fn check_names(checks: &[Check]) -> String {
+    checks.iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+// Single check given by ID or by a query matching exactly one check
+fn select_check(client: &ApiClient, cache: &Cache, id: &str) -> Result<Check, Box<dyn std::error::Error>> {
+    let mut checks = select_checks(client, cache, &[id.to_string()])?;
+    if checks.len() > 1 {
+        return Err(Error::Ambiguous { id: id.to_string(), candidates: checks }.into());
+    }
+    Ok(checks.remove(0))
+}
+
+fn cmd_show_check(client: &ApiClient, cache: &Cache, id: &str, output: OutputFormat) -> Result<(), Box<dyn std::error::Error>> {
+    let c = select_check(client, cache, id)?;
+    if output != OutputFormat::Table {
+        return print_checks(&[c], output);
+    }
+
+    let status = if io::stdout().is_terminal() { colored_status(&c.status) } else { c.status.normal() };
+
+    println!("name:      {}", c.name);
+    println!("id:        {}", c.id());
+    println!("status:    {}", status);
+    if !c.desc.is_empty() {
+        println!("desc:      {}", c.desc);
+    }
+    println!("tags:      {}", c.tags);
+    println!("schedule:  {}", describe_schedule(&c));
+    print_next_runs(&c, "next runs: ");
+    println!("grace:     {}", format_duration(c.grace as i64));
+    println!("last ping: {}", c.humanized_last_ping_at());
+    println!("next ping: {}", c.humanized_next_ping_at());
+    println!("pings:     {}", c.n_pings);
+    if !c.ping_url.is_empty() {
+        println!("ping URL:  {}", c.ping_url);
+    }
+
+    Ok(())
+}
+
+// Period of simple checks in seconds, range is validated by the API client
+fn parse_period(period: &str) -> Result<u32, Error> {
+    let d = parse_duration(period)?;
+    u32::try_from(d.num_seconds()).map_err(|_| Error::Invalid(format!("Invalid duration: {}", period)))
+}
+
+// Grace period in seconds, a bare number is hours as in earlier versions
+fn parse_grace(grace: &str) -> Result<u32, Error> {
+    if let Ok(hours) = grace.parse::<u32>() {
+        return hours.checked_mul(3600).ok_or_else(|| Error::Invalid(format!("Invalid duration: {}", grace)));
+    }
+    parse_period(grace)
+}
+
+// Positional schedule may be cron or OnCalendar, told apart as healthchecks does
+fn schedule_kind(schedule: Option<&str>, every: Option<&str>, oncalendar: Option<&str>, tz: Option<&str>) -> Result<ScheduleKind, Error> {
+    let tz = tz.unwrap_or("UTC").to_string();
+    match (schedule, every, oncalendar) {
+        (_, Some(every), _) => Ok(ScheduleKind::Simple { timeout: parse_period(every)? }),
+        (_, _, Some(expr)) => Ok(ScheduleKind::OnCalendar { expr: expr.to_string(), tz }),
+        (Some(expr), _, _) => Ok(ScheduleKind::from_expr(expr, &tz)),
+        _ => Err(Error::Invalid("Either schedule, --every or --oncalendar is required".to_string()))
+    }
+}
+
+// "every 1h 0m", "cron 0 3 * * * (UTC)" or "oncalendar Mon..Fri 02:00 (UTC)"
+fn describe_schedule(check: &Check) -> String {
+    match &check.schedule {
+        ScheduleKind::Simple { timeout } => format!("every {}", format_duration(*timeout as i64)),
+        ScheduleKind::Cron { expr, tz } => format!("cron {} ({})", expr, tz),
+        ScheduleKind::OnCalendar { expr, tz } => {
+            format!("oncalendar {} ({})", expr.lines().collect::<Vec<_>>().join(", "), tz)
+        }
+        // kinds added by newer library versions
+        schedule => schedule.expr().unwrap_or("-").to_string()
+    }
+}
+
+// Firing times previewed for OnCalendar schedules
+const NEXT_RUNS: usize = 3;
+
+// Next firing times of OnCalendar schedules, in schedule's time zone
+fn next_runs(schedule: &ScheduleKind, n: usize) -> Vec<String> {
+    let ScheduleKind::OnCalendar { expr, tz } = schedule else {
+        return Vec::new();
+    };
+    let (Ok(oncalendar), Ok(tz)) = (OnCalendar::parse(expr), parse_tz(tz)) else {
+        return Vec::new();
+    };
+
+    oncalendar.upcoming(Utc::now(), tz, n).iter()
+        .map(|t| t.with_timezone(&tz).format("%a %Y-%m-%d %H:%M:%S %Z").to_string())
+        .collect()
+}
+
+fn print_next_runs(check: &Check, label: &str) {
+    for (i, t) in next_runs(&check.schedule, NEXT_RUNS).iter().enumerate() {
+        let label = if i == 0 { label.to_string() } else { " ".repeat(label.len()) };
+        println!("{}{}", label, t);
+    }
+}
+
+fn cmd_add_check(client: &ApiClient, name: &str, schedule: &ScheduleKind, grace: Option<&str>, tags: Option<&str>, output: OutputFormat) -> Result<(), Box<dyn std::error::Error>> {
+    let grace_v = parse_grace(grace.unwrap_or("1"))?;
+
+    let check = client.add(name, schedule, grace_v, tags)?;
+    if output != OutputFormat::Table {
+        return print_checks(&[check], output);
+    }
+
+    println!("{} {} {}", check.name, check.uuid, check.ping_url);
+    print_next_runs(&check, "next runs: ");
+
+    Ok(())
+}
+
+// Print "field: old -> new" for every field that differs between the two checks
+fn print_check_diff(before: &Check, after: &Check) -> Result<(), Box<dyn std::error::Error>> {
+    let before = serde_json::to_value(before)?;
+    let after = serde_json::to_value(after)?;
+
+    let (Some(before), Some(after)) = (before.as_object(), after.as_object()) else {
+        return Ok(());
+    };
+
+    let tty = io::stdout().is_terminal();
+    for (field, old) in before {
+        let new = &after[field];
+        if old == new {
             continue
         }
 
-        let mut status = colored_status(&c.status);
+        let mut old_s = old.to_string().red();
+        let mut new_s = new.to_string().green();
         if !tty {
-            status = status.clear();
+            old_s = old_s.clear();
+            new_s = new_s.clear();
         }
+        println!("{}: {} -> {}", field, old_s, new_s);
+    }
+
+    Ok(())
+}
 
-        println!("{status:<s_width$} {id:<id_width$} {name:<n_width$} {last_ping:<lp_width$}",
-                 name=c.name,
-                 status=status,
-                 id=c.short_uuid,
-                 last_ping=c.humanized_last_ping_at(),
-                 s_width=6, id_width=9, n_width=40, lp_width=30);
+fn cmd_edit_check(client: &ApiClient, cache: &Cache, id: &str, patch: CheckPatch, output: OutputFormat) -> Result<(), Box<dyn std::error::Error>> {
+    let c = select_check(client, cache, id)?;
+
+    let updated = client.update(&c, patch)?;
+    if output != OutputFormat::Table {
+        return print_checks(&[updated], output);
+    }
+
+    print_check_diff(&c, &updated)?;
+    if updated.schedule != c.schedule {
+        print_next_runs(&updated, "next runs: ");
     }
 
     Ok(())
 }
 
-fn cmd_add_check(client: &ApiClient, name: &str, schedule: &str, grace: Option<&str>, tz: Option<&str>, tags: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
-    let grace_s = grace.unwrap_or("1");
-    let grace_v = grace_s.parse::<u32>()
-        .map_err(|_| format!("Grace period must be a valid number, got: {}", grace_s))?;
+// Print a note, keeping stdout clean for machine-readable output
+fn notice(msg: &str, output: OutputFormat) {
+    if output == OutputFormat::Table {
+        println!("{}", msg);
+    } else {
+        eprintln!("{}", msg);
+    }
+}
+
+fn cmd_pause_checks(client: &ApiClient, cache: &Cache, ids: &[String], output: OutputFormat) -> Result<(), Box<dyn std::error::Error>> {
+    let mut results = Vec::new();
+    for c in select_checks(client, cache, ids)? {
+        if c.status == "paused" {
+            notice(&format!("{}: check is already paused", c.name), output);
+            results.push(c);
+            continue
+        }
+        results.push(client.pause(&c)?);
+    }
+
+    print_checks(&results, output)
+}
+
+fn cmd_resume_checks(client: &ApiClient, cache: &Cache, ids: &[String], tag: Option<&str>, output: OutputFormat) -> Result<(), Box<dyn std::error::Error>> {
+    let mut checks = Vec::new();
+    if !ids.is_empty() {
+        checks = select_checks(client, cache, ids)?;
+    }
 
-    let check = client.add(name, schedule, grace_v, tz, tags)?;
-    println!("{} {} {}", check.name, check.uuid, check.ping_url);
+    if let Some(tag) = tag {
+        let tagged = list_checks(client, cache, &[tag])?;
+
+        if tagged.is_empty() {
+            return Err(Error::NotFound(format!("tag:{}", tag)).into());
+        }
+        checks.extend(tagged);
+    }
+
+    // Checks given by ID and tagged are resumed once
+    let mut seen = HashSet::new();
+    checks.retain(|c| seen.insert(c.id().to_string()));
+
+    let tty = io::stdout().is_terminal();
+    let mut results = Vec::new();
+    for c in checks {
+        if c.status != "paused" {
+            notice(&format!("{}: check is not paused", c.name), output);
+            results.push(c);
+            continue
+        }
+
+        let c = client.resume(&c)?;
+        if output == OutputFormat::Table {
+            let mut status = colored_status(&c.status);
+            if !tty {
+                status = status.clear();
+            }
+            println!("{}: {}", c.name, status);
+        }
+        results.push(c);
+    }
+
+    print_checks(&results, output)
+}
+
+// Parse duration like "90s", "30m", "2h", "7d" or "1w"
+fn parse_duration(s: &str) -> Result<TimeDelta, Error> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+
+    let n = num.parse::<i64>()
+        .map_err(|_| Error::Invalid(format!("Invalid duration: {}", s)))?;
+
+    let d = match unit {
+        "s" => TimeDelta::try_seconds(n),
+        "m" => TimeDelta::try_minutes(n),
+        "h" => TimeDelta::try_hours(n),
+        "d" => TimeDelta::try_days(n),
+        "w" => TimeDelta::try_weeks(n),
+        _ => None
+    };
+
+    d.ok_or_else(|| Error::Invalid(format!("Invalid duration: {} (use s, m, h, d or w suffix)", s)))
+}
+
+// Parse point in time given as duration ago or as RFC 3339 date
+fn parse_time(s: &str) -> Result<DateTime<Local>, Error> {
+    if let Ok(d) = parse_duration(s) {
+        return Ok(Local::now() - d);
+    }
+
+    DateTime::parse_from_rfc3339(s)
+        .map(|dt| dt.with_timezone(&Local))
+        .map_err(|_| Error::Invalid(format!("Invalid time: {} (use duration like 2h or RFC 3339 date)", s)))
+}
+
+fn colored_ping_kind(kind: &str) -> ColoredString {
+    match kind {
+        "success" => kind.green(),
+        "fail" => kind.red(),
+        "start" => kind.cyan(),
+        "log" => kind.yellow(),
+        _ => kind.white(),
+    }
+}
+
+struct LogFlags<'a> {
+    limit: Option<usize>,
+    kind: Option<&'a str>,
+    since: Option<&'a str>,
+    until: Option<&'a str>
+}
+
+fn cmd_log_check(client: &ApiClient, cache: &Cache, id: &str, flags: &LogFlags) -> Result<(), Box<dyn std::error::Error>> {
+    let c = select_check(client, cache, id)?;
+
+    let since = flags.since.map(parse_time).transpose()?;
+    let until = flags.until.map(parse_time).transpose()?;
+
+    let mut pings = client.pings(&c)?;
+    pings.retain(|p| {
+        let date = p.date_at();
+        flags.kind.is_none_or(|k| p.kind == k) &&
+            since.is_none_or(|s| date >= s) &&
+            until.is_none_or(|u| date <= u)
+    });
+
+    if let Some(limit) = flags.limit {
+        pings.truncate(limit);
+    }
+
+    let tty = io::stdout().is_terminal();
+    for p in pings {
+        let mut kind = colored_ping_kind(&p.kind);
+        if !tty {
+            kind = kind.clear();
+        }
+
+        let duration = p.duration.map(|d| format!("{:.1}s", d)).unwrap_or_default();
+        let exitstatus = p.exitstatus.map(|e| e.to_string()).unwrap_or_default();
+
+        println!("{n:>6} {kind:<k_width$} {date:<d_width$} {duration:>8} {exitstatus:>4} {addr:<a_width$} {method:<6} {ua}",
+                 n=p.n,
+                 kind=kind,
+                 date=p.date_at().format("%Y-%m-%d %H:%M:%S").to_string(),
+                 duration=duration,
+                 exitstatus=exitstatus,
+                 addr=p.remote_addr,
+                 method=p.method,
+                 ua=p.ua,
+                 k_width=7, d_width=19, a_width=15);
+    }
+
+    Ok(())
+}
+
+// Healthchecks cuts bodies at PING_BODY_LIMIT bytes, 10 kB by default and 100 kB on healthchecks.io
+const PING_BODY_LIMITS: [usize; 2] = [10_000, 100_000];
+
+fn cmd_ping_body(client: &ApiClient, cache: &Cache, id: &str, n: Option<u32>) -> Result<(), Box<dyn std::error::Error>> {
+    let c = select_check(client, cache, id)?;
+
+    let n = match n {
+        Some(n) => n,
+        None => client.pings(&c)?
+            .iter()
+            .find(|p| p.kind == "fail")
+            .map(|p| p.n)
+            .ok_or_else(|| format!("{}: no failed pings", c.name))?
+    };
+
+    let body = client.ping_body(&c, n)?;
+
+    let mut stdout = io::stdout();
+    match std::str::from_utf8(&body) {
+        Ok(text) => {
+            print!("{}", text);
+            if !text.is_empty() && !text.ends_with('\n') {
+                println!();
+            }
+        }
+        Err(_) if stdout.is_terminal() => {
+            return Err(format!("ping #{} has a binary body ({} bytes), redirect output to a file to save it", n, body.len()).into());
+        }
+        Err(_) => stdout.write_all(&body)?
+    }
+
+    if PING_BODY_LIMITS.contains(&body.len()) {
+        eprintln!("{}", format!("ping #{} body is {} bytes, it was probably truncated by the server", n, body.len()).yellow());
+    }
 
     Ok(())
 }
 
-fn cmd_pause_check(client: &ApiClient, id: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
-    let id = id.ok_or("ID is required")?;
+// Format seconds as "2d 3h", "1h 5m", "45s"
+fn format_duration(secs: i64) -> String {
+    let (d, h, m, s) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60, secs % 60);
+    match (d, h, m) {
+        (0, 0, 0) => format!("{}s", s),
+        (0, 0, _) => format!("{}m {}s", m, s),
+        (0, _, _) => format!("{}h {}m", h, m),
+        _ => format!("{}d {}h", d, h),
+    }
+}
+
+#[derive(Serialize)]
+struct ReportRow {
+    name: String,
+    uuid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unique_key: Option<String>,
+    #[serde(flatten)]
+    uptime: Uptime,
+}
+
+fn cmd_report(client: &ApiClient, since: &str, tag: Option<&str>, query: &[String], json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let end = Local::now();
+    let start = end - parse_duration(since)?;
+    let filter = Filter::from_args(query)?;
 
-    let c = client.find(id)
-        .ok_or_else(|| format!("{}: check not found", id))?;
+    let tags: Vec<&str> = tag.into_iter().collect();
+    let mut checks = client.get_tagged(None, &tags)?;
+    checks.retain(|c| filter.matches(c));
+    checks.sort_by(|a, b| a.name.cmp(&b.name));
 
-    if c.status == "paused" {
-        println!("{}: check is already paused", c.name);
+    let mut rows = Vec::new();
+    for c in checks {
+        let flips = client.flips(&c, Some(start))?;
+        let uptime = Uptime::from_flips(&flips, start, end, c.status != "down");
+        rows.push(ReportRow { name: c.name, uuid: c.uuid, unique_key: c.unique_key, uptime });
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&rows)?);
         return Ok(());
     }
 
-    client.pause(&c)?;
+    println!("{name:<n_width$} {uptime:>8} {outages:>7} {longest:>10} {mttr:>10}",
+             name="NAME", uptime="UPTIME", outages="OUTAGES", longest="LONGEST", mttr="MTTR",
+             n_width=40);
+
+    for r in rows {
+        println!("{name:<n_width$} {uptime:>7.3}% {outages:>7} {longest:>10} {mttr:>10}",
+                 name=r.name,
+                 uptime=r.uptime.uptime,
+                 outages=r.uptime.outages,
+                 longest=format_duration(r.uptime.longest_outage),
+                 mttr=format_duration(r.uptime.mttr),
+                 n_width=40);
+    }
+
     Ok(())
 }
 
-fn cmd_ping_check(client: &ApiClient, id: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
-    let id = id.ok_or("ID is required")?;
+// Keeps the last `limit` bytes written to it
+struct OutputTail {
+    buf: VecDeque<u8>,
+    limit: usize
+}
+
+impl OutputTail {
+    fn push(&mut self, data: &[u8]) {
+        self.buf.extend(data);
+        let excess = self.buf.len().saturating_sub(self.limit);
+        self.buf.drain(..excess);
+    }
+}
+
+// Copy everything from reader to out, keeping a copy in tail
+fn tee<R: Read, W: Write>(mut reader: R, mut out: W, tail: Arc<Mutex<OutputTail>>) {
+    let mut buf = [0u8; 8192];
+    while let Ok(n) = reader.read(&mut buf) {
+        if n == 0 {
+            break
+        }
+        let _ = out.write_all(&buf[..n]);
+        let _ = out.flush();
+        tail.lock().unwrap().push(&buf[..n]);
+    }
+}
+
+#[cfg(unix)]
+fn exit_code(status: &process::ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+    status.code().or_else(|| status.signal().map(|s| 128 + s)).unwrap_or(1)
+}
+
+#[cfg(not(unix))]
+fn exit_code(status: &process::ExitStatus) -> i32 {
+    status.code().unwrap_or(1)
+}
+
+// Pings `ping_url`, spooling the ping when the endpoint is unreachable. Pings
+// spooled before are delivered after a successful one
+fn send_ping(client: &ApiClient, spool: &Spool, ping_url: &str, kind: PingKind, body: Option<&[u8]>, rid: Option<Uuid>) -> Result<(), Error> {
+    match client.ping_url(ping_url, kind, body, rid) {
+        Ok(()) => {
+            flush_spool(client, spool);
+            Ok(())
+        }
+        Err(e) if e.is_transient() => {
+            match spool.push(&SpooledPing::new(ping_url, kind, body, rid)) {
+                Ok(_) => eprintln!("hchk: ping spooled in {}, to be delivered later", spool.dir.display()),
+                Err(spool_err) => eprintln!("hchk: failed to spool ping: {}", spool_err),
+            }
+            Err(e)
+        }
+        Err(e) => Err(e)
+    }
+}
+
+fn flush_spool(client: &ApiClient, spool: &Spool) {
+    match spool.flush(client) {
+        Ok(stats) if stats.sent > 0 => eprintln!("hchk: delivered {} spooled pings", stats.sent),
+        Ok(_) => {}
+        Err(e) => eprintln!("hchk: failed to flush spooled pings: {}", e),
+    }
+}
 
-    let c = client.find(id)
-        .ok_or_else(|| format!("{}: check not found", id))?;
+fn cmd_flush(client: &ApiClient, spool: &Spool) -> Result<(), Box<dyn std::error::Error>> {
+    let stats = spool.flush(client)?;
+    if stats.busy {
+        return Err(format!("another flush is running, {} pings left in {}", stats.pending, spool.dir.display()).into());
+    }
+    println!("{} sent, {} duplicates, {} expired, {} dropped, {} pending",
+        stats.sent, stats.duplicates, stats.expired, stats.dropped, stats.pending);
 
-    client.ping(&c)?;
+    if stats.pending > 0 {
+        return Err(format!("{} pings left in {}, ping endpoint is unreachable", stats.pending, spool.dir.display()).into());
+    }
     Ok(())
 }
 
-fn cmd_delete_check(client: &ApiClient, id: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
-    let id = id.ok_or("ID is required")?;
+// Ping URL of check to run the command for, None if the check can't be looked up
+fn run_ping_url(client: &ApiClient, cache: &Cache, id: &str) -> Option<String> {
+    if is_url(id) {
+        return Some(id.to_string());
+    }
+    if is_uuid(id) {
+        return Some(client.uuid_ping_url(id));
+    }
+
+    match select_check(client, cache, id) {
+        Ok(c) => Some(c.ping_url),
+        Err(e) => {
+            eprintln!("hchk: {}: running command without monitoring: {}", id, e);
+            None
+        }
+    }
+}
+
+fn cmd_run_check(client: &ApiClient, cache: &Cache, spool: &Spool, id: &str, capture_kb: usize, command: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    // Monitoring problems must not prevent the job from running
+    let ping_url = run_ping_url(client, cache, id);
+    let rid = Uuid::new_v4();
+    let ping = |kind: PingKind, body: Option<&[u8]>| {
+        let Some(url) = &ping_url else {
+            return
+        };
+        if let Err(e) = send_ping(client, spool, url, kind, body, Some(rid)) {
+            eprintln!("hchk: {}: ping failed: {}", id, e);
+        }
+    };
+
+    ping(PingKind::Start, None);
+
+    let child = Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            let msg = format!("{}: {}", command[0], e);
+            ping(PingKind::Fail, Some(msg.as_bytes()));
+            return Err(msg.into());
+        }
+    };
+
+    let tail = Arc::new(Mutex::new(OutputTail { buf: VecDeque::new(), limit: capture_kb * 1024 }));
+    let stdout = child.stdout.take().unwrap();
+    let stderr = child.stderr.take().unwrap();
+
+    let out_tail = tail.clone();
+    let out_thread = thread::spawn(move || tee(stdout, io::stdout(), out_tail));
+    let err_tail = tail.clone();
+    let err_thread = thread::spawn(move || tee(stderr, io::stderr(), err_tail));
+
+    let status = child.wait()?;
+    let _ = out_thread.join();
+    let _ = err_thread.join();
+
+    let code = exit_code(&status);
+    let output: Vec<u8> = tail.lock().unwrap().buf.iter().copied().collect();
+    let kind = match status.code().and_then(|c| u8::try_from(c).ok()) {
+        Some(code) => PingKind::ExitStatus(code),
+        None => PingKind::Fail
+    };
+
+    ping(kind, Some(&output));
+
+    process::exit(code);
+}
+
+fn read_body_file(path: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut body = Vec::new();
+    if path == "-" {
+        io::stdin().read_to_end(&mut body)?;
+    } else {
+        File::open(path)?.read_to_end(&mut body)?;
+    }
+    Ok(body)
+}
+
+fn is_url(id: &str) -> bool {
+    id.starts_with("https://") || id.starts_with("http://")
+}
+
+// Pings addressed by UUID, URL or slug go straight to the ping endpoint
+fn is_direct_ping(id: Option<&str>, slug: Option<&str>) -> bool {
+    slug.is_some() || id.is_some_and(|id| is_url(id) || is_uuid(id))
+}
+
+struct PingFlags<'a> {
+    kind: PingKind,
+    body_file: Option<&'a str>,
+    rid: Option<Uuid>,
+    create: bool
+}
 
-    let c = client.find(id)
-        .ok_or_else(|| format!("{}: check not found", id))?;
+fn cmd_ping_check(client: &ApiClient, cache: &Cache, spool: &Spool, id: Option<&str>, ping_key: Option<&str>, slug: Option<&str>, flags: &PingFlags) -> Result<(), Box<dyn std::error::Error>> {
+    let url = match (id, slug) {
+        (_, Some(slug)) => {
+            let ping_key = ping_key.ok_or("Ping key is required to ping by slug")?;
+            client.slug_ping_url(ping_key, slug, flags.create)
+        }
+        (Some(id), None) if is_url(id) => id.to_string(),
+        (Some(id), None) if is_uuid(id) => client.uuid_ping_url(id),
+        (Some(id), None) => select_check(client, cache, id)?.ping_url,
+        (None, None) => return Err("ID is required".into())
+    };
 
-    client.delete(&c)?;
+    let body = flags.body_file.map(read_body_file).transpose()?;
+    send_ping(client, spool, &url, flags.kind, body.as_deref(), flags.rid)?;
     Ok(())
 }
 
-fn keyfile_path() -> String {
+fn cmd_delete_checks(client: &ApiClient, cache: &Cache, ids: &[String], yes: bool, output: OutputFormat) -> Result<(), Box<dyn std::error::Error>> {
+    // Query terms are not IDs, any check matched by a query is deleted only with --yes
+    let (checks, by_query) = select_checks_by(client, cache, ids)?;
+    if (by_query || checks.len() > ids.len()) && !yes {
+        return Err(format!("query matches {} checks ({}), use --yes to delete them all", checks.len(), check_names(&checks)).into());
+    }
+
+    let mut results = Vec::new();
+    for c in checks {
+        results.push(client.delete(&c)?);
+    }
+
+    print_checks(&results, output)
+}
+
+fn keyfile_path(name: &str) -> String {
     let home = env::var("HOME");
     if home.is_err() {
         println!("empty HOME environment variable");
         process::exit(1);
     }
 
-    home.unwrap() + "/.hchk"
+    home.unwrap() + "/" + name
 }
 
-fn cmd_setkey(key: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
-    let key = key.ok_or("API key is required")?;
+fn cmd_setkey(profile_name: &str, values: Profile) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = Config::load()?;
+    let profile = config.profile_mut(profile_name);
 
-    let path = keyfile_path();
-    let mut file = File::create(&path)?;
-    file.write_all(key.trim().as_bytes())?;
-
-    // Set file permissions to 0o600 (read/write for owner only) on Unix
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let permissions = std::fs::Permissions::from_mode(0o600);
-        std::fs::set_permissions(&path, permissions)?;
+    let trimmed = |v: Option<String>| v.map(|v| v.trim().to_string());
+    if values.api_key.is_some() {
+        profile.api_key = trimmed(values.api_key);
+    }
+    if values.read_only_key.is_some() {
+        profile.read_only_key = trimmed(values.read_only_key);
+    }
+    if values.ping_key.is_some() {
+        profile.ping_key = trimmed(values.ping_key);
+    }
+    if values.api_url.is_some() {
+        profile.api_url = trimmed(values.api_url);
+    }
+    if values.ping_url.is_some() {
+        profile.ping_url = trimmed(values.ping_url);
     }
 
-    Ok(())
+    config.save()
 }
 
-const API_KEY_ENV: &str = "HCHK_API_KEY";
-fn get_api_key() -> Result<String, Box<dyn std::error::Error>> {
-    let key = env::var(API_KEY_ENV);
-
-    if key.is_err() {
-        let path = keyfile_path();
-        if Path::new(&path).is_file() {
-            let mut file = File::open(path)?;
-            let mut contents = String::new();
-            file.read_to_string(&mut contents)?;
-            return Ok(contents.trim().to_string());
-        }
+// Keyfiles in $HOME used before config profiles, read for the default profile only
+const API_KEY_FILE: &str = ".hchk";
+const PING_KEY_FILE: &str = ".hchk-ping-key";
+
+// Read key from environment variable, profile or legacy keyfile
+fn read_key(env_name: &str, profile_key: Option<&String>, legacy_file: Option<&str>) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    if let Ok(key) = env::var(env_name) {
+        return Ok(Some(key));
+    }
+
+    if let Some(key) = profile_key {
+        return Ok(Some(key.clone()));
     }
 
-    if key.is_err() {
-        return Err(format!("Use setkey command or set {} environment variable", API_KEY_ENV).into());
+    let Some(legacy_file) = legacy_file else {
+        return Ok(None);
+    };
+
+    let path = keyfile_path(legacy_file);
+    if Path::new(&path).is_file() {
+        let mut file = File::open(path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        return Ok(Some(contents.trim().to_string()));
     }
 
-    Ok(key.unwrap())
+    Ok(None)
+}
+
+fn legacy_keyfile<'a>(profile_name: &str, file_name: &'a str) -> Option<&'a str> {
+    (profile_name == DEFAULT_PROFILE).then_some(file_name)
+}
+
+const API_KEY_ENV: &str = "HCHK_API_KEY";
+fn get_api_key(profile_name: &str, profile: &Profile) -> Result<String, Box<dyn std::error::Error>> {
+    read_key(API_KEY_ENV, profile.api_key.as_ref(), legacy_keyfile(profile_name, API_KEY_FILE))?
+        .ok_or_else(|| format!("Use setkey command or set {} environment variable", API_KEY_ENV).into())
+}
+
+// Key for read-only commands, falls back to profile's read-only key
+fn get_read_key(profile_name: &str, profile: &Profile) -> Result<String, Box<dyn std::error::Error>> {
+    get_api_key(profile_name, profile).or_else(|e| profile.read_only_key.clone().ok_or(e))
 }
 
-fn run(cmd: &Commands) -> Result<(), Box<dyn std::error::Error>> {
+const PING_KEY_ENV: &str = "HCHK_PING_KEY";
+fn get_ping_key(profile_name: &str, profile: &Profile) -> Result<String, Box<dyn std::error::Error>> {
+    read_key(PING_KEY_ENV, profile.ping_key.as_ref(), legacy_keyfile(profile_name, PING_KEY_FILE))?
+        .ok_or_else(|| format!("Use --ping-key option, setkey --ping-key command or set {} environment variable", PING_KEY_ENV).into())
+}
+
+// Pings should survive a short network or server outage
+const DEFAULT_RETRIES: u32 = 3;
+// Pings delayed by more than a day tell little about the job
+const DEFAULT_SPOOL_MAX_AGE: &str = "1d";
+const DEFAULT_CACHE_TTL: &str = "5m";
+
+fn run(cmd: &Commands, profile_name: &str, retries: Option<u32>, no_cache: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let profile = Config::load()?.profile(profile_name);
+
     let key = match cmd {
-        Commands::Setkey { .. } => "".to_string(),
-        _ => get_api_key()?
+        Commands::Setkey { .. } | Commands::Flush { .. } => "".to_string(),
+        Commands::Ping { id, slug, .. } if is_direct_ping(id.as_deref(), slug.as_deref()) => "".to_string(),
+        Commands::Run { id, .. } if is_direct_ping(Some(id), None) => "".to_string(),
+        // Without the key the job still runs, just not monitored
+        Commands::Run { .. } => get_api_key(profile_name, &profile).unwrap_or_else(|e| {
+            eprintln!("hchk: {}", e);
+            String::new()
+        }),
+        Commands::Ls { .. } | Commands::Report { .. } => get_read_key(profile_name, &profile)?,
+        _ => get_api_key(profile_name, &profile)?
+    };
+
+    let mut client = ApiClient::new(&key, profile.checks_url().as_deref())?;
+    if let Some(url) = profile.ping_base_url() {
+        client.ping_base_url = url;
+    }
+    client.retry = RetryPolicy::new(retries.or(profile.retries).unwrap_or(DEFAULT_RETRIES));
+
+    let max_age = match cmd {
+        Commands::Flush { max_age: Some(max_age) } => max_age,
+        _ => profile.spool_max_age.as_deref().unwrap_or(DEFAULT_SPOOL_MAX_AGE)
+    };
+    let spool = Spool::new(Spool::default_dir()?, parse_duration(max_age)?);
+
+    let cache = if no_cache {
+        Cache::disabled()
+    } else {
+        let ttl = parse_duration(profile.cache_ttl.as_deref().unwrap_or(DEFAULT_CACHE_TTL))?;
+        let path = cache_dir()?.join(format!("checks-{}.json", profile_name));
+        Cache::new(path, ttl, &client.base_url, &key)
     };
 
-    let client = ApiClient::new(&key, None);
+    let result = run_command(cmd, profile_name, &profile, &client, &cache, &spool);
+
+    // Even failed changes may have changed some of the checks
+    if matches!(cmd, Commands::Add { .. } | Commands::Edit { .. } | Commands::Pause { .. } | Commands::Resume { .. } | Commands::Del { .. }) {
+        cache.invalidate();
+    }
+
+    result
+}
 
+fn run_command(cmd: &Commands, profile_name: &str, profile: &Profile, client: &ApiClient, cache: &Cache, spool: &Spool) -> Result<(), Box<dyn std::error::Error>> {
     match cmd {
-        Commands::Ls { long, up, down, query } => {
+        Commands::Ls { long, up, down, query, tags, columns, format, output } => {
             let flags = LsFlags {
                 long: *long,
                 up: *up,
                 down: *down,
+                output: *output,
+                columns,
+                format: format.as_deref(),
+                tags,
             };
-            cmd_list_checks(&client, &flags, query.as_deref())
+            cmd_list_checks(client, cache, &flags, query)
         }
-        Commands::Add { name, schedule, grace, tz, tags } => {
+        Commands::Show { id, output } => cmd_show_check(client, cache, id, *output),
+        Commands::Add { name, schedule, grace, tz, tags, every, oncalendar, tz_opt, grace_opt, tags_opt, output } => {
+            let tz = tz.as_deref().or(tz_opt.as_deref());
+            let schedule = schedule_kind(schedule.as_deref(), every.as_deref(), oncalendar.as_deref(), tz)?;
             cmd_add_check(
-                &client,
+                client,
                 name,
-                schedule,
-                grace.as_deref(),
-                tz.as_deref(),
-                tags.as_deref(),
+                &schedule,
+                grace.as_deref().or(grace_opt.as_deref()),
+                tags.as_deref().or(tags_opt.as_deref()),
+                *output,
             )
         }
-        Commands::Ping { id } => cmd_ping_check(&client, Some(id)),
-        Commands::Pause { id } => cmd_pause_check(&client, Some(id)),
-        Commands::Del { id } => cmd_delete_check(&client, Some(id)),
-        Commands::Setkey { key } => cmd_setkey(key.as_deref()),
+        Commands::Edit { id, name, desc, schedule, every, oncalendar, grace, tz, tags, output } => {
+            // Checked here, the library would take a cron-like expression for cron
+            if let Some(expr) = oncalendar {
+                OnCalendar::parse(expr)?;
+            }
+            let patch = CheckPatch {
+                name: name.clone(),
+                desc: desc.clone(),
+                schedule: schedule.clone().or(oncalendar.clone()),
+                timeout: every.as_deref().map(parse_period).transpose()?,
+                grace: grace.as_deref().map(parse_grace).transpose()?,
+                tz: tz.clone(),
+                tags: tags.clone(),
+            };
+            cmd_edit_check(client, cache, id, patch, *output)
+        }
+        Commands::Log { id, body: Some(n), .. } => cmd_ping_body(client, cache, id, *n),
+        Commands::Log { id, limit, kind, since, until, .. } => {
+            let flags = LogFlags {
+                limit: *limit,
+                kind: kind.as_deref(),
+                since: since.as_deref(),
+                until: until.as_deref(),
+            };
+            cmd_log_check(client, cache, id, &flags)
+        }
+        Commands::Report { since, tag, query, json } => cmd_report(client, since, tag.as_deref(), query, *json),
+        Commands::Run { id, capture_kb, command } => cmd_run_check(client, cache, spool, id, *capture_kb, command),
+        Commands::Ping { id, ping_key, slug, create, start, fail, log, exit_code, body_file, rid } => {
+            let ping_key = match (ping_key, slug) {
+                (Some(key), _) => Some(key.clone()),
+                (None, Some(_)) => Some(get_ping_key(profile_name, profile)?),
+                _ => None
+            };
+            let kind = match (start, fail, log, exit_code) {
+                (true, _, _, _) => PingKind::Start,
+                (_, true, _, _) => PingKind::Fail,
+                (_, _, true, _) => PingKind::Log,
+                (_, _, _, Some(code)) => PingKind::ExitStatus(*code),
+                _ => PingKind::Success
+            };
+            let flags = PingFlags {
+                kind,
+                body_file: body_file.as_deref(),
+                rid: *rid,
+                create: *create,
+            };
+            cmd_ping_check(client, cache, spool, id.as_deref(), ping_key.as_deref(), slug.as_deref(), &flags)
+        }
+        Commands::Pause { ids, output } => cmd_pause_checks(client, cache, ids, *output),
+        Commands::Resume { ids, tag, output } => cmd_resume_checks(client, cache, ids, tag.as_deref(), *output),
+        Commands::Del { ids, yes, output } => cmd_delete_checks(client, cache, ids, *yes, *output),
+        Commands::Flush { .. } => cmd_flush(client, spool),
+        Commands::Setkey { key, ping_key, read_only_key, api_url, ping_url } => {
+            let values = Profile {
+                api_key: key.clone(),
+                read_only_key: read_only_key.clone(),
+                ping_key: ping_key.clone(),
+                api_url: api_url.clone(),
+                ping_url: ping_url.clone(),
+                ..Default::default()
+            };
+            cmd_setkey(profile_name, values)
+        }
+    }
+}
+
+const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  1   error
+  2   invalid arguments
+  3   unauthorized, invalid API key
+  4   check not found
+  5   ambiguous check ID
+  6   rate limited
+  7   conflict
+  8   other API error
+  9   network error
+  10  invalid API response";
+
+// Exit code of failed command, see EXIT_CODES_HELP
+fn error_exit_code(e: &(dyn std::error::Error + 'static)) -> i32 {
+    match e.downcast_ref::<Error>() {
+        Some(Error::Invalid(_)) => 2,
+        Some(Error::Unauthorized) => 3,
+        Some(Error::NotFound(_)) => 4,
+        Some(Error::Ambiguous { .. }) => 5,
+        Some(Error::RateLimited { .. }) => 6,
+        Some(Error::Conflict(_)) => 7,
+        Some(Error::Server(..)) => 8,
+        Some(Error::Network(_)) => 9,
+        Some(Error::Decode(_)) => 10,
+        _ => 1
     }
 }
 
@@ -263,13 +1369,13 @@ fn main() {
 
     // Handle the subcommand if present
     let result = if let Some(command) = &cli.command {
-        run(command)
+        run(command, &config::active_profile(cli.profile.as_deref()), cli.retries, cli.no_cache)
     } else {
         Ok(())
     };
 
     if let Err(e) = result {
         eprintln!("Error: {}", e);
-        process::exit(1);
+        process::exit(error_exit_code(e.as_ref()));
     }
 }
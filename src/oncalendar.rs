@@ -0,0 +1,255 @@
+// systemd OnCalendar expressions, the subset accepted by healthchecks as check
+// schedules: "[weekdays] [date] [time] [time zone]", like "Mon..Fri 02:00" or
+// "*-*-01 06:00:00 Europe/Warsaw", and the shorthands like "daily". Several
+// expressions may be given on separate lines.
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use crate::error::Error;
+
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+const WEEKDAY_NAMES: [&str; 7] = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+const MIN_YEAR: u32 = 1970;
+const MAX_YEAR: u32 = 2199;
+
+/// Parsed OnCalendar expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct OnCalendar {
+    specs: Vec<Spec>,
+}
+
+// A single line of the expression, each field lists allowed values in order
+#[derive(Debug, Clone, PartialEq)]
+struct Spec {
+    weekdays: [bool; 7],
+    years: Vec<u32>,
+    months: Vec<u32>,
+    days: Vec<u32>,
+    hours: Vec<u32>,
+    minutes: Vec<u32>,
+    seconds: Vec<u32>,
+    tz: Option<Tz>,
+}
+
+fn invalid(expr: &str, reason: &str) -> Error {
+    Error::Invalid(format!("Invalid OnCalendar expression: {} ({})", expr, reason))
+}
+
+/// Parses IANA time zone name like "Europe/Warsaw"
+pub fn parse_tz(tz: &str) -> Result<Tz, Error> {
+    tz.parse().map_err(|_| Error::Invalid(format!("Unknown time zone: {}", tz)))
+}
+
+fn shorthand(word: &str) -> Option<&'static str> {
+    let expanded = match word.to_ascii_lowercase().as_str() {
+        "minutely" => "*-*-* *:*:00",
+        "hourly" => "*-*-* *:00:00",
+        "daily" => "*-*-* 00:00:00",
+        "weekly" => "Mon *-*-* 00:00:00",
+        "monthly" => "*-*-01 00:00:00",
+        "quarterly" => "*-01,04,07,10-01 00:00:00",
+        "semiannually" => "*-01,07-01 00:00:00",
+        "yearly" | "annually" => "*-01-01 00:00:00",
+        _ => return None
+    };
+    Some(expanded)
+}
+
+fn weekday(name: &str) -> Option<usize> {
+    let name = name.to_ascii_lowercase();
+    WEEKDAYS.iter().position(|d| *d == name)
+        .or_else(|| WEEKDAY_NAMES.iter().position(|d| *d == name))
+}
+
+// "Mon..Fri,Sun", ranges may wrap around the week like "Sat..Mon"
+fn parse_weekdays(s: &str) -> Option<[bool; 7]> {
+    let mut days = [false; 7];
+    for item in s.split(',') {
+        let (from, to) = match item.split_once("..") {
+            Some((from, to)) => (weekday(from)?, weekday(to)?),
+            None => (weekday(item)?, weekday(item)?)
+        };
+        let mut d = from;
+        loop {
+            days[d] = true;
+            if d == to {
+                break
+            }
+            d = (d + 1) % 7;
+        }
+    }
+    Some(days)
+}
+
+// Comma separated values, "a..b" ranges and "a/step" or "a..b/step" repetitions
+fn parse_values(s: &str, min: u32, max: u32) -> Option<Vec<u32>> {
+    let mut values = Vec::new();
+    for item in s.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, Some(step.parse::<u32>().ok().filter(|s| *s > 0)?)),
+            None => (item, None)
+        };
+
+        let (from, to) = match range.split_once("..") {
+            _ if range == "*" => (min, max),
+            Some((from, to)) => (from.parse().ok()?, to.parse().ok()?),
+            None => {
+                let v = range.parse().ok()?;
+                (v, if step.is_some() { max } else { v })
+            }
+        };
+        if from < min || to > max || from > to {
+            return None;
+        }
+        values.extend((from..=to).step_by(step.unwrap_or(1) as usize));
+    }
+
+    values.sort_unstable();
+    values.dedup();
+    Some(values)
+}
+
+// Two digit years are 1970-2069, as in systemd
+fn parse_years(s: &str) -> Option<Vec<u32>> {
+    if s.split([',', '.', '/']).all(|p| p.len() == 2) {
+        let years = parse_values(s, 0, 99)?;
+        return Some(years.into_iter().map(|y| if y < 70 { 2000 + y } else { 1900 + y }).collect());
+    }
+    parse_values(s, MIN_YEAR, MAX_YEAR)
+}
+
+impl Spec {
+    fn parse(line: &str) -> Result<Spec, Error> {
+        let err = |reason| invalid(line, reason);
+
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        let mut tz = None;
+        if words.len() > 1 && let Ok(last) = parse_tz(words[words.len() - 1]) {
+            tz = Some(last);
+            words.pop();
+        }
+
+        if let [word] = words[..] && let Some(expanded) = shorthand(word) {
+            return Ok(Spec { tz, ..Spec::parse(expanded)? });
+        }
+
+        let mut spec = Spec {
+            weekdays: [true; 7],
+            years: parse_values("*", MIN_YEAR, MAX_YEAR).unwrap_or_default(),
+            months: parse_values("*", 1, 12).unwrap_or_default(),
+            days: parse_values("*", 1, 31).unwrap_or_default(),
+            hours: vec![0],
+            minutes: vec![0],
+            seconds: vec![0],
+            tz,
+        };
+
+        let mut words = words.into_iter().peekable();
+        if let Some(word) = words.next_if(|w| w.starts_with(|c: char| c.is_ascii_alphabetic())) {
+            spec.weekdays = parse_weekdays(word).ok_or_else(|| err("invalid weekday"))?;
+        }
+
+        if let Some(word) = words.next_if(|w| w.contains('-')) {
+            if word.contains('~') {
+                return Err(err("last day of month is not supported"));
+            }
+            let parts: Vec<&str> = word.split('-').collect();
+            let (years, month, day) = match parts[..] {
+                [y, m, d] => (Some(y), m, d),
+                [m, d] => (None, m, d),
+                _ => return Err(err("invalid date"))
+            };
+            if let Some(years) = years {
+                spec.years = parse_years(years).ok_or_else(|| err("invalid year"))?;
+            }
+            spec.months = parse_values(month, 1, 12).ok_or_else(|| err("invalid month"))?;
+            spec.days = parse_values(day, 1, 31).ok_or_else(|| err("invalid day"))?;
+        }
+
+        if let Some(word) = words.next_if(|w| w.contains(':')) {
+            let parts: Vec<&str> = word.split(':').collect();
+            let (hours, minutes, seconds) = match parts[..] {
+                [h, m] => (h, m, "0"),
+                [h, m, s] => (h, m, s),
+                _ => return Err(err("invalid time"))
+            };
+            spec.hours = parse_values(hours, 0, 23).ok_or_else(|| err("invalid hour"))?;
+            spec.minutes = parse_values(minutes, 0, 59).ok_or_else(|| err("invalid minute"))?;
+            spec.seconds = parse_values(seconds, 0, 59).ok_or_else(|| err("invalid second"))?;
+        }
+
+        if let Some(word) = words.next() {
+            return Err(err(&format!("unexpected \"{}\"", word)));
+        }
+        Ok(spec)
+    }
+
+    // First time matching the spec later than `after`
+    fn next_after(&self, after: DateTime<Utc>, tz: Tz) -> Option<DateTime<Utc>> {
+        let tz = self.tz.unwrap_or(tz);
+        let start = after.with_timezone(&tz).date_naive();
+
+        for &year in self.years.iter().filter(|y| **y as i32 >= start.year()) {
+            for &month in &self.months {
+                for &day in &self.days {
+                    let Some(date) = NaiveDate::from_ymd_opt(year as i32, month, day) else {
+                        continue
+                    };
+                    if date < start || !self.weekdays[date.weekday().num_days_from_monday() as usize] {
+                        continue
+                    }
+
+                    for &h in &self.hours {
+                        for &m in &self.minutes {
+                            for &s in &self.seconds {
+                                // Times skipped by DST changes never fire
+                                let local = date.and_hms_opt(h, m, s)?;
+                                let Some(t) = tz.from_local_datetime(&local).earliest() else {
+                                    continue
+                                };
+                                let t = t.with_timezone(&Utc);
+                                if t > after {
+                                    return Some(t);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl OnCalendar {
+    /// Parses expression, one or more lines of it
+    pub fn parse(expr: &str) -> Result<OnCalendar, Error> {
+        let specs = expr.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(Spec::parse)
+            .collect::<Result<Vec<Spec>, Error>>()?;
+
+        if specs.is_empty() {
+            return Err(invalid(expr, "empty expression"));
+        }
+        Ok(OnCalendar { specs })
+    }
+
+    /// First firing after `after`, in time zone `tz` unless the expression gives its own
+    pub fn next_after(&self, after: DateTime<Utc>, tz: Tz) -> Option<DateTime<Utc>> {
+        self.specs.iter().filter_map(|s| s.next_after(after, tz)).min()
+    }
+
+    /// Up to `n` next firings after `after`, see `next_after`
+    pub fn upcoming(&self, after: DateTime<Utc>, tz: Tz, n: usize) -> Vec<DateTime<Utc>> {
+        let mut times = Vec::new();
+        let mut t = after;
+        while times.len() < n {
+            let Some(next) = self.next_after(t, tz) else {
+                break
+            };
+            times.push(next);
+            t = next;
+        }
+        times
+    }
+}
@@ -0,0 +1,184 @@
+use std::io::{self, Write};
+use clap::ValueEnum;
+use serde_json::Value;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+use hchk::Check;
+
+/// Output format of commands printing checks
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable table
+    #[default]
+    Table,
+    /// JSON array of checks
+    Json,
+    /// One JSON object per line
+    Ndjson,
+    Csv,
+    Yaml,
+}
+
+// Columns of CSV output, kept fixed so scripts can rely on them
+const CSV_FIELDS: [&str; 16] = [
+    "uuid", "slug", "name", "desc", "status", "tags", "grace", "timeout", "schedule",
+    "tz", "n_pings", "last_ping", "next_ping", "ping_url", "update_url", "pause_url"
+];
+
+fn csv_value(v: &Value) -> String {
+    match v {
+        Value::Null => "".to_string(),
+        Value::String(s) => s.clone(),
+        v => v.to_string()
+    }
+}
+
+/// Writes checks in given machine-readable format, `Table` is left to the caller
+pub fn write_checks<W: Write>(out: &mut W, checks: &[Check], format: OutputFormat) -> Result<(), Box<dyn std::error::Error>> {
+    match format {
+        OutputFormat::Table => {}
+        OutputFormat::Json => {
+            writeln!(out, "{}", serde_json::to_string_pretty(checks)?)?;
+        }
+        OutputFormat::Ndjson => {
+            for c in checks {
+                writeln!(out, "{}", serde_json::to_string(c)?)?;
+            }
+        }
+        OutputFormat::Csv => {
+            let mut w = csv::Writer::from_writer(out);
+            w.write_record(CSV_FIELDS)?;
+            for c in checks {
+                let v = serde_json::to_value(c)?;
+                w.write_record(CSV_FIELDS.iter().map(|f| csv_value(&v[*f])))?;
+            }
+            w.flush()?;
+        }
+        OutputFormat::Yaml => {
+            write!(out, "{}", serde_yaml::to_string(checks)?)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn print_checks(checks: &[Check], format: OutputFormat) -> Result<(), Box<dyn std::error::Error>> {
+    write_checks(&mut io::stdout().lock(), checks, format)
+}
+
+/// Columns shown by `ls` when none are given
+pub const DEFAULT_COLUMNS: [&str; 4] = ["status", "id", "name", "last_ping"];
+
+// Value of check's field as it appears in JSON output, "id" is the short UUID
+fn field(check: &Check, name: &str) -> Option<String> {
+    if name == "id" {
+        return Some(check.short_uuid.clone());
+    }
+
+    let v = serde_json::to_value(check).ok()?;
+    v.get(name).map(csv_value)
+}
+
+/// Checks that all columns name check's fields
+pub fn validate_columns(columns: &[String]) -> Result<(), String> {
+    for c in columns {
+        if c != "id" && !CSV_FIELDS.contains(&c.as_str()) {
+            return Err(format!("Unknown column: {} (available: id, {})", c, CSV_FIELDS.join(", ")));
+        }
+    }
+    Ok(())
+}
+
+/// Value of table cell, timestamps and durations are humanized, schedule tells its kind
+pub fn cell(check: &Check, column: &str) -> String {
+    match column {
+        "last_ping" => check.humanized_last_ping_at(),
+        "next_ping" => check.humanized_next_ping_at(),
+        "grace" => crate::format_duration(check.grace as i64),
+        "timeout" => check.schedule.timeout().map(|t| crate::format_duration(t as i64)).unwrap_or_default(),
+        "schedule" => crate::describe_schedule(check),
+        _ => field(check, column).unwrap_or_default()
+    }
+}
+
+/// Width of each column so that rows fit in `max_width`, the widest
+/// columns are narrowed first
+pub fn column_widths(rows: &[Vec<String>], max_width: Option<usize>) -> Vec<usize> {
+    const MIN_WIDTH: usize = 6;
+
+    let n = rows.first().map(|r| r.len()).unwrap_or(0);
+    let mut widths: Vec<usize> = (0..n)
+        .map(|i| rows.iter().map(|r| r[i].width()).max().unwrap_or(0))
+        .collect();
+
+    let Some(max_width) = max_width else {
+        return widths;
+    };
+
+    let separators = n.saturating_sub(1);
+    while widths.iter().sum::<usize>() + separators > max_width {
+        let Some(widest) = widths.iter_mut().filter(|w| **w > MIN_WIDTH).max() else {
+            break
+        };
+        *widest -= 1;
+    }
+
+    widths
+}
+
+/// Pads or truncates `s` to exactly `width` terminal columns
+pub fn fit(s: &str, width: usize) -> String {
+    if s.width() <= width {
+        return format!("{}{}", s, " ".repeat(width - s.width()));
+    }
+
+    let mut out = String::new();
+    let mut w = 0;
+    for c in s.chars() {
+        let cw = c.width().unwrap_or(0);
+        if w + cw + 1 > width {
+            break
+        }
+        out.push(c);
+        w += cw;
+    }
+    out.push('…');
+    format!("{}{}", out, " ".repeat(width.saturating_sub(w + 1)))
+}
+
+pub fn terminal_width() -> Option<usize> {
+    terminal_size::terminal_size().map(|(w, _)| w.0 as usize)
+}
+
+/// Renders check using template like "{name}\t{ping_url}", with raw field values
+pub fn format_check(check: &Check, template: &str) -> Result<String, String> {
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('t') => out.push('\t'),
+                Some('n') => out.push('\n'),
+                Some(c) => out.push(c),
+                None => out.push('\\'),
+            },
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let name: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                let value = field(check, &name)
+                    .ok_or_else(|| format!("Unknown field in format: {{{}}}", name))?;
+                out.push_str(&value);
+            }
+            c => out.push(c),
+        }
+    }
+
+    Ok(out)
+}
@@ -0,0 +1,67 @@
+use std::time::Duration;
+use uuid::Uuid;
+use crate::error::Error;
+
+// Longer Retry-After is not worth waiting for, the error is returned instead
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// How `ApiClient` retries pings and idempotent requests failing with network
+/// errors, server errors (HTTP 5xx) or rate limiting (HTTP 429)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt, 0 disables retrying
+    pub retries: u32,
+    /// Delay before the first retry, doubled with each next one
+    pub base_delay: Duration,
+    /// Upper limit of the delay, except for delays requested with Retry-After
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Policy with given number of retries, starting at half a second and
+    /// backing off up to 10 seconds
+    pub fn new(retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            retries,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+
+    /// No retries, failed requests return the error right away
+    pub fn none() -> RetryPolicy {
+        RetryPolicy::new(0)
+    }
+
+    /// Delay before retrying request that failed with `error` after `attempt`
+    /// retries, `None` when it should not be retried
+    pub fn delay(&self, attempt: u32, error: &Error) -> Option<Duration> {
+        if attempt >= self.retries {
+            return None;
+        }
+
+        match error {
+            Error::RateLimited { retry_after: Some(secs) } => {
+                let delay = Duration::from_secs(*secs);
+                (delay <= MAX_RETRY_AFTER).then_some(delay)
+            }
+            e if e.is_transient() => Some(self.backoff(attempt)),
+            _ => None
+        }
+    }
+
+    // Exponential backoff with jitter, between half and full of the delay
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+
+        let half = delay / 2;
+        half + half.mul_f64(jitter())
+    }
+}
+
+// Random number in 0..1, random UUIDs spare a dependency on rand
+fn jitter() -> f64 {
+    (Uuid::new_v4().as_u128() as u32) as f64 / u32::MAX as f64
+}
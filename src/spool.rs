@@ -0,0 +1,199 @@
+// Pings that could not be delivered, kept in ~/.cache/hchk/spool until `hchk flush`
+// or the next successful ping. Each ping is a JSON file named after the UTC time it
+// was spooled, so that sorting by name gives the delivery order.
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::Duration;
+use chrono::{DateTime, Local, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use hchk::{ApiClient, PingKind};
+use crate::cache::cache_dir;
+use crate::config::write_private;
+
+// Lock of a flush which didn't finish within this time is considered stale
+const LOCK_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SpooledPing {
+    pub ping_url: String,
+    pub kind: PingKind,
+    #[serde(default)]
+    pub body: Option<Vec<u8>>,
+    #[serde(default)]
+    pub rid: Option<String>,
+    /// Time of the original ping, RFC 3339
+    pub created: String,
+}
+
+impl SpooledPing {
+    pub fn new(ping_url: &str, kind: PingKind, body: Option<&[u8]>, rid: Option<Uuid>) -> SpooledPing {
+        SpooledPing {
+            ping_url: ping_url.to_string(),
+            kind,
+            body: body.map(|b| b.to_vec()),
+            rid: rid.map(|r| r.to_string()),
+            created: Local::now().to_rfc3339(),
+        }
+    }
+
+    fn created_at(&self) -> Option<DateTime<Local>> {
+        DateTime::parse_from_rfc3339(&self.created).ok().map(|dt| dt.with_timezone(&Local))
+    }
+
+    // Pings are dated by their delivery, the original time goes to the body
+    fn delivery_body(&self) -> Vec<u8> {
+        let mut body = self.body.clone().unwrap_or_default();
+        if !body.is_empty() && !body.ends_with(b"\n") {
+            body.push(b'\n');
+        }
+        body.extend(format!("[hchk: ping delayed, originally sent at {}]\n", self.created).as_bytes());
+        body
+    }
+}
+
+/// Outcome of `Spool::flush`
+#[derive(Debug, Default, PartialEq)]
+pub struct FlushStats {
+    pub sent: usize,
+    pub duplicates: usize,
+    pub expired: usize,
+    /// Rejected by the server, e.g. pings of deleted checks
+    pub dropped: usize,
+    /// Left in the spool, the ping endpoint is still unreachable or another flush delivers them
+    pub pending: usize,
+    /// Another flush was running, the spool was left to it
+    pub busy: bool,
+}
+
+pub struct Spool {
+    pub dir: PathBuf,
+    /// Pings older than this are discarded instead of delivered
+    pub max_age: TimeDelta,
+}
+
+// Removes the lock file when flush is done
+struct Lock(PathBuf);
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+impl Spool {
+    pub fn new(dir: PathBuf, max_age: TimeDelta) -> Spool {
+        Spool { dir, max_age }
+    }
+
+    /// $XDG_CACHE_HOME/hchk/spool or ~/.cache/hchk/spool
+    pub fn default_dir() -> Result<PathBuf, String> {
+        Ok(cache_dir()?.join("spool"))
+    }
+
+    pub fn push(&self, ping: &SpooledPing) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        fs::create_dir_all(&self.dir)?;
+
+        // UTC, local time repeats an hour when DST ends
+        let name = format!("{}-{}.json", Utc::now().format("%Y%m%dT%H%M%S%.9f"), Uuid::new_v4().simple());
+        let path = self.dir.join(name);
+        // Ping URLs are secrets of a kind
+        write_private(&path, &serde_json::to_vec(ping)?)?;
+        Ok(path)
+    }
+
+    /// Spooled pings, oldest first. Unreadable files are skipped
+    pub fn entries(&self) -> Result<Vec<(PathBuf, SpooledPing)>, Box<dyn std::error::Error>> {
+        if !self.dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut paths: Vec<PathBuf> = fs::read_dir(&self.dir)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+
+        let entries = paths.into_iter()
+            .filter_map(|p| {
+                let ping = fs::read(&p).ok().and_then(|data| serde_json::from_slice(&data).ok())?;
+                Some((p, ping))
+            })
+            .collect();
+        Ok(entries)
+    }
+
+    // None if another flush is running
+    fn lock(&self) -> Result<Option<Lock>, Box<dyn std::error::Error>> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(".lock");
+
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(Some(Lock(path))),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    let age = fs::metadata(&path)?.modified()?.elapsed().unwrap_or_default();
+                    if age < LOCK_TIMEOUT {
+                        return Ok(None);
+                    }
+                    fs::remove_file(&path)?;
+                }
+                Err(e) => return Err(e.into())
+            }
+        }
+        Ok(None)
+    }
+
+    /// Delivers spooled pings in order, discarding duplicates and those older than
+    /// `max_age`. Stops at the first failure suggesting the endpoint is still
+    /// unreachable, leaving the rest for the next flush
+    pub fn flush(&self, client: &ApiClient) -> Result<FlushStats, Box<dyn std::error::Error>> {
+        let mut stats = FlushStats::default();
+        if self.entries()?.is_empty() {
+            return Ok(stats);
+        }
+
+        // Entries are read under the lock, another flush may have delivered them meanwhile
+        let Some(_lock) = self.lock()? else {
+            stats.pending = self.entries()?.len();
+            stats.busy = true;
+            return Ok(stats);
+        };
+        let entries = self.entries()?;
+
+        let now = Local::now();
+        let mut seen = HashSet::new();
+        let total = entries.len();
+
+        for (i, (path, ping)) in entries.iter().enumerate() {
+            let expired = ping.created_at().is_none_or(|t| now - t > self.max_age);
+            if expired {
+                stats.expired += 1;
+            } else if !seen.insert((&ping.ping_url, ping.kind, &ping.rid, &ping.body)) {
+                stats.duplicates += 1;
+            } else {
+                let rid = ping.rid.as_deref().and_then(|r| Uuid::try_parse(r).ok());
+                match client.ping_url(&ping.ping_url, ping.kind, Some(&ping.delivery_body()), rid) {
+                    Ok(()) => stats.sent += 1,
+                    Err(e) if e.is_transient() => {
+                        stats.pending = total - i;
+                        break
+                    }
+                    Err(e) => {
+                        eprintln!("hchk: dropping spooled ping of {}: {}", ping.ping_url, e);
+                        stats.dropped += 1;
+                    }
+                }
+            }
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+
+        Ok(stats)
+    }
+}
+
@@ -1,501 +1,742 @@
-// Unit tests for the API module
+// Check as listed by the API, tests adjust the fields they need
 #[cfg(test)]
-mod api_tests {
-    use crate::api::*;
-    use chrono::prelude::*;
-    use mockito::{Matcher, Server};
+fn check(uuid: &str, name: &str) -> hchk::Check {
+    serde_json::from_value(serde_json::json!({
+        "uuid": uuid, "name": name, "slug": name, "grace": 3600, "n_pings": 0,
+        "tags": "", "status": "up", "last_ping": null, "next_ping": null,
+        "timeout": null, "tz": null, "schedule": null,
+        "ping_url": format!("https://hc-ping.com/{}", uuid)
+    })).unwrap()
+}
 
-    fn sample_check_json() -> String {
-        r#"{
-            "uuid": "abc123-def456",
-            "slug": "test-check",
-            "name": "test-check",
-            "ping_url": "https://hc-ping.com/abc123-def456",
-            "pause_url": "https://healthchecks.io/api/v1/checks/abc123-def456/pause",
-            "last_ping": "2024-01-01T12:00:00+00:00",
-            "next_ping": "2024-01-01T13:00:00+00:00",
-            "grace": 3600,
-            "n_pings": 10,
-            "tags": "test",
-            "timeout": 86400,
-            "tz": "UTC",
-            "schedule": "0 * * * *",
-            "status": "up",
-            "update_url": "https://healthchecks.io/api/v1/checks/abc123-def456"
-        }"#
-        .to_string()
-    }
-
-    fn sample_checks_response() -> String {
-        format!(r#"{{"checks": [{}]}}"#, sample_check_json())
-    }
-
-    fn create_test_check(uuid: &str) -> Check {
-        Check {
-            uuid: uuid.to_string(),
-            short_uuid: "".to_string(),
-            name: "test".to_string(),
-            slug: "test".to_string(),
-            ping_url: format!("https://hc-ping.com/{}", uuid),
-            pause_url: "".to_string(),
-            last_ping: None,
-            next_ping: None,
-            grace: 3600,
-            n_pings: 0,
-            tags: "".to_string(),
-            timeout: None,
-            tz: None,
-            schedule: None,
-            status: "up".to_string(),
-            update_url: "".to_string(),
-        }
-    }
+// Unit tests for the command line helpers
+#[cfg(test)]
+mod cli_tests {
+    use crate::*;
 
     #[test]
-    fn test_check_short_uuid() {
-        let mut check = create_test_check("abc123-def456");
-        check.set_short_uuid();
-
-        assert_eq!(check.short_uuid, "abc123");
+    fn test_error_exit_code() {
+        use hchk::Error;
+
+        let code = |e: Error| error_exit_code(&e);
+        assert_eq!(code(Error::Unauthorized), 3);
+        assert_eq!(code(Error::NotFound("backup".to_string())), 4);
+        assert_eq!(code(Error::Ambiguous { id: "backup".to_string(), candidates: vec![] }), 5);
+        assert_eq!(code(Error::RateLimited { retry_after: None }), 6);
+        assert_eq!(code(Error::Server(500, String::new())), 8);
+
+        // arguments checked by the command line tool itself
+        assert_eq!(code(parse_duration("soon").unwrap_err()), 2);
+        assert_eq!(code(crate::filter::Filter::parse("colour:red").unwrap_err()), 2);
+
+        let other: Box<dyn std::error::Error> = "ping endpoint is unreachable".into();
+        assert_eq!(error_exit_code(other.as_ref()), 1);
     }
 
     #[test]
-    fn test_check_last_ping_at() {
-        let mut check = create_test_check("abc123-def456");
-        check.last_ping = Some("2024-01-15T10:30:00+00:00".to_string());
-
-        let last_ping = check.last_ping_at();
-        assert!(last_ping.year() >= 2024);
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s").unwrap(), TimeDelta::seconds(90));
+        assert_eq!(parse_duration("30m").unwrap(), TimeDelta::minutes(30));
+        assert_eq!(parse_duration("2h").unwrap(), TimeDelta::hours(2));
+        assert_eq!(parse_duration("7d").unwrap(), TimeDelta::days(7));
+        assert_eq!(parse_duration("1w").unwrap(), TimeDelta::weeks(1));
     }
 
     #[test]
-    fn test_check_last_ping_at_none() {
-        let check = create_test_check("abc123-def456");
+    fn test_parse_duration_invalid() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("10y").is_err());
+    }
 
-        let last_ping = check.last_ping_at();
-        assert_eq!(last_ping.year(), 1901);
-        assert_eq!(last_ping.month(), 1);
-        assert_eq!(last_ping.day(), 1);
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(45), "45s");
+        assert_eq!(format_duration(65), "1m 5s");
+        assert_eq!(format_duration(3900), "1h 5m");
+        assert_eq!(format_duration(2 * 86400 + 3 * 3600), "2d 3h");
     }
 
     #[test]
-    fn test_check_humanized_last_ping_at() {
-        let mut check = create_test_check("abc123-def456");
-        check.last_ping = Some("2024-01-15T10:30:00+00:00".to_string());
+    fn test_parse_grace() {
+        assert_eq!(parse_grace("2").unwrap(), 7200);
+        assert_eq!(parse_grace("15m").unwrap(), 900);
+        assert!(parse_grace("soon").is_err());
+    }
 
-        let humanized = check.humanized_last_ping_at();
-        assert!(humanized.len() > 0);
+    #[test]
+    fn test_add_every() {
+        let cli = Cli::try_parse_from(["hchk", "add", "backup", "--every", "1h", "--grace", "15m"]).unwrap();
+        let Some(Commands::Add { schedule, every, grace_opt, .. }) = cli.command else {
+            panic!("expected add command");
+        };
+        assert_eq!(schedule_kind(schedule.as_deref(), every.as_deref(), None, None).unwrap(), ScheduleKind::Simple { timeout: 3600 });
+        assert_eq!(grace_opt.as_deref(), Some("15m"));
+
+        assert!(Cli::try_parse_from(["hchk", "add", "backup", "0 3 * * *", "--every", "1h"]).is_err());
+        assert!(Cli::try_parse_from(["hchk", "add", "backup"]).is_err());
+        assert_eq!(
+            schedule_kind(Some("0 3 * * *"), None, None, Some("Europe/Warsaw")).unwrap(),
+            ScheduleKind::Cron { expr: "0 3 * * *".to_string(), tz: "Europe/Warsaw".to_string() }
+        );
     }
 
     #[test]
-    fn test_api_client_new() {
-        let client = ApiClient::new("test-key", Some("https://example.com/api/"));
-        assert_eq!(client.base_url, "https://example.com/api/");
+    fn test_add_oncalendar() {
+        let cli = Cli::try_parse_from(["hchk", "add", "backup", "--oncalendar", "Mon..Fri 02:00", "--tz", "Europe/Warsaw"]).unwrap();
+        let Some(Commands::Add { schedule, every, oncalendar, tz_opt, .. }) = cli.command else {
+            panic!("expected add command");
+        };
+        let kind = schedule_kind(schedule.as_deref(), every.as_deref(), oncalendar.as_deref(), tz_opt.as_deref()).unwrap();
+        assert_eq!(kind, ScheduleKind::OnCalendar { expr: "Mon..Fri 02:00".to_string(), tz: "Europe/Warsaw".to_string() });
+        assert_eq!(next_runs(&kind, 3).len(), 3);
+        assert!(next_runs(&kind, 1)[0].starts_with(['M', 'T', 'W', 'F']));
+
+        // Positional schedules are told apart by how they look
+        assert!(matches!(schedule_kind(Some("*-*-* 02:00"), None, None, None), Ok(ScheduleKind::OnCalendar { .. })));
+        assert!(Cli::try_parse_from(["hchk", "edit", "backup", "--oncalendar", "daily", "--every", "1d"]).is_err());
     }
 
     #[test]
-    fn test_api_client_add() {
-        let mut server = Server::new();
-        let mock = server
-            .mock("POST", "/")
-            .match_header("X-Api-Key", "test-key")
-            .match_body(Matcher::JsonString(r#"{"grace":3600,"name":"test-check","schedule":"0 * * * *","tags":"","tz":"UTC","unique":["name"]}"#.to_string()))
+    fn test_select_checks_name_like_query() {
+        let mut server = mockito::Server::new();
+        let listing = server.mock("GET", "/")
             .with_status(200)
-            .with_header("content-type", "application/json")
-            .with_body(sample_check_json())
+            .with_body(r#"{"checks": [{
+                "uuid": "0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d", "name": "prod: backup", "slug": "prod-backup",
+                "grace": 3600, "n_pings": 0, "tags": "", "status": "up", "last_ping": null, "next_ping": null
+            }]}"#)
+            .expect(3)
             .create();
 
-        let client = ApiClient::new("test-key", Some(&server.url()));
-        let result = client.add("test-check", "0 * * * *", 1, None, None);
+        let client = ApiClient::new("key", Some(&format!("{}/", server.url()))).unwrap();
+        let cache = crate::cache::Cache::disabled();
+        let checks = select_checks(&client, &cache, &["prod: backup".to_string()]).unwrap();
+        assert_eq!(checks[0].slug, "prod-backup");
 
-        mock.assert();
-        assert!(result.is_ok());
-        let check = result.unwrap();
-        assert_eq!(check.name, "test-check");
-        assert_eq!(check.status, "up");
+        // neither a check nor a valid query, the query error tells more
+        let err = select_checks(&client, &cache, &["staus:down".to_string()]).unwrap_err();
+        assert!(err.to_string().contains("Invalid query term"));
+        assert_eq!(error_exit_code(err.as_ref()), 2);
+
+        let err = select_checks(&client, &cache, &["status:down".to_string()]).unwrap_err();
+        assert_eq!(error_exit_code(err.as_ref()), 4);
+        listing.assert();
     }
 
     #[test]
-    fn test_api_client_add_with_tags_and_tz() {
-        let mut server = Server::new();
-        let mock = server
-            .mock("POST", "/")
-            .match_header("X-Api-Key", "test-key")
-            .match_body(Matcher::JsonString(r#"{"grace":7200,"name":"test-check","schedule":"0 * * * *","tags":"prod,critical","tz":"America/New_York","unique":["name"]}"#.to_string()))
+    fn test_delete_by_query_needs_yes() {
+        let mut tmp = super::check("0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d", "tmp-down");
+        tmp.tags = "tmp".to_string();
+        tmp.status = "down".to_string();
+
+        let mut server = mockito::Server::new();
+        server.mock("GET", "/")
+            .with_status(200)
+            .with_body(format!(r#"{{"checks": [{}]}}"#, serde_json::to_string(&tmp).unwrap()))
+            .create();
+        let delete = server.mock("DELETE", "/0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d")
             .with_status(200)
-            .with_header("content-type", "application/json")
-            .with_body(sample_check_json())
+            .with_body(serde_json::to_string(&tmp).unwrap())
+            .expect(1)
             .create();
 
-        let client = ApiClient::new("test-key", Some(&server.url()));
-        let result = client.add(
-            "test-check",
-            "0 * * * *",
-            2,
-            Some("America/New_York"),
-            Some("prod,critical"),
-        );
+        let client = ApiClient::new("key", Some(&format!("{}/", server.url()))).unwrap();
+        let cache = crate::cache::Cache::disabled();
+        // two query terms matching a single check are not two IDs
+        let query = ["tag:tmp".to_string(), "status:down".to_string()];
+        let err = cmd_delete_checks(&client, &cache, &query, false, OutputFormat::Json).unwrap_err();
+        assert!(err.to_string().contains("use --yes"));
+        assert!(cmd_delete_checks(&client, &cache, &query[..1], false, OutputFormat::Json).is_err());
 
-        mock.assert();
-        assert!(result.is_ok());
+        cmd_delete_checks(&client, &cache, &query, true, OutputFormat::Json).unwrap();
+        delete.assert();
     }
 
     #[test]
-    fn test_api_client_delete() {
-        let mut server = Server::new();
-        let mock = server
-            .mock("DELETE", "/abc123-def456")
-            .match_header("X-Api-Key", "test-key")
+    fn test_resume_by_id_and_tag() {
+        let uuid = "0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d";
+        let mut paused = super::check(uuid, "backup");
+        paused.tags = "db".to_string();
+        paused.status = "paused".to_string();
+        let paused = serde_json::to_string(&paused).unwrap();
+
+        let mut server = mockito::Server::new();
+        server.mock("GET", format!("/{}", uuid).as_str())
+            .with_status(200)
+            .with_body(paused.clone())
+            .create();
+        server.mock("GET", "/")
+            .match_query(mockito::Matcher::Exact("tag=db".to_string()))
+            .with_status(200)
+            .with_body(format!(r#"{{"checks": [{}]}}"#, paused))
+            .create();
+        let resume = server.mock("POST", format!("/{}/resume", uuid).as_str())
             .with_status(200)
-            .with_header("content-type", "application/json")
-            .with_body(sample_check_json())
+            .with_body(serde_json::to_string(&super::check(uuid, "backup")).unwrap())
+            .expect(1)
             .create();
 
-        let base_url = format!("{}/", server.url());
-        let client = ApiClient::new("test-key", Some(&base_url));
-        let check = create_test_check("abc123-def456");
+        let client = ApiClient::new("key", Some(&format!("{}/", server.url()))).unwrap();
+        let cache = crate::cache::Cache::disabled();
+        cmd_resume_checks(&client, &cache, &[uuid.to_string()], Some("db"), OutputFormat::Json).unwrap();
+        resume.assert();
+    }
 
-        let result = client.delete(&check);
-        mock.assert();
-        assert!(result.is_ok());
+    #[test]
+    fn test_list_checks_tagged_short_ids() {
+        let mut prod = super::check("0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d", "backup");
+        prod.tags = "prod".to_string();
+        let staging = super::check("0a1b2c3d-9999-4a6b-8c7d-9e0f1a2b3c4d", "backup");
+
+        let mut server = mockito::Server::new();
+        let tagged = server.mock("GET", "/")
+            .match_query(mockito::Matcher::Exact("tag=prod".to_string()))
+            .with_status(200)
+            .with_body(format!(r#"{{"checks": [{}]}}"#, serde_json::to_string(&prod).unwrap()))
+            .expect(2)
+            .create();
+        // the full listing is never downloaded only for short IDs
+        let listing = server.mock("GET", "/")
+            .match_query(mockito::Matcher::Missing)
+            .expect(0)
+            .create();
+
+        let client = ApiClient::new("key", Some(&format!("{}/", server.url()))).unwrap();
+        let checks = list_checks(&client, &crate::cache::Cache::disabled(), &["prod"]).unwrap();
+        assert_eq!(checks.len(), 1);
+        assert_eq!(checks[0].short_uuid, "0a1b2c3d");
+
+        // with a fresh cache unique among all checks, "0a1b2c3d" alone would be ambiguous
+        let path = std::env::temp_dir().join(format!("hchk-cache-{}", Uuid::new_v4())).join("checks-default.json");
+        let cache = crate::cache::Cache::new(path.clone(), TimeDelta::minutes(5), &server.url(), "key");
+        cache.store(&[prod, staging]);
+        let checks = list_checks(&client, &cache, &["prod"]).unwrap();
+        assert_eq!(checks[0].short_uuid, "0a1b2c3d-4");
+
+        tagged.assert();
+        listing.assert();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
     }
 
     #[test]
-    fn test_api_client_ping() {
-        let mut server = Server::new();
-        let ping_url = format!("{}/ping", server.url());
+    fn test_run_ping_url() {
+        // nothing listens on port 1, the API is down
+        let client = ApiClient::new("key", Some("http://127.0.0.1:1/")).unwrap();
+        let cache = crate::cache::Cache::disabled();
+
+        let uuid = "0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d";
+        assert_eq!(run_ping_url(&client, &cache, uuid), Some(format!("https://hc-ping.com/{}", uuid)));
+        assert_eq!(run_ping_url(&client, &cache, "https://hc.example.com/ping/abc"), Some("https://hc.example.com/ping/abc".to_string()));
+        assert_eq!(run_ping_url(&client, &cache, "backup"), None);
+    }
 
-        let mock = server.mock("GET", "/ping").with_status(200).create();
+    #[test]
+    fn test_is_direct_ping() {
+        assert!(is_direct_ping(Some("0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d"), None));
+        assert!(is_direct_ping(Some("https://hc-ping.com/0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d"), None));
+        assert!(is_direct_ping(None, Some("backup")));
+        assert!(!is_direct_ping(Some("backup"), None));
+    }
 
-        let client = ApiClient::new("test-key", Some(&server.url()));
-        //let client = ApiClient::new(&server.url(), "test-key");
-        let mut check = create_test_check("abc123-def456");
-        check.ping_url = ping_url;
+    #[test]
+    fn test_output_tail() {
+        let mut tail = OutputTail { buf: VecDeque::new(), limit: 4 };
+        tail.push(b"abc");
+        tail.push(b"def");
+        assert_eq!(tail.buf.iter().copied().collect::<Vec<u8>>(), b"cdef");
+    }
 
-        let result = client.ping(&check);
-        mock.assert();
-        assert!(result.is_ok());
+    #[test]
+    fn test_parse_time() {
+        let t = parse_time("2024-01-15T10:30:00+00:00").unwrap();
+        assert_eq!(t.timestamp(), 1705314600);
+        assert!(parse_time("2h").unwrap() < Local::now());
+        assert!(parse_time("yesterday").is_err());
     }
+}
+
+// Unit tests for the config file
+#[cfg(test)]
+mod config_tests {
+    use crate::config::*;
 
     #[test]
-    fn test_api_client_pause() {
-        let mut server = Server::new();
-        let mock = server
-            .mock("POST", "/abc123-def456/pause")
-            .match_header("X-Api-Key", "test-key")
-            .with_status(200)
-            .with_header("content-type", "application/json")
-            .with_body(
-                r#"{
-                "uuid": "abc123-def456",
-                "name": "test-check",
-                "slug": "test-check",
-                "ping_url": "https://hc-ping.com/abc123-def456",
-                "pause_url": "https://healthchecks.io/api/v1/checks/abc123-def456/pause",
-                "last_ping": null,
-                "next_ping": null,
-                "grace": 3600,
-                "n_pings": 0,
-                "tags": "",
-                "timeout": null,
-                "tz": null,
-                "schedule": null,
-                "status": "paused",
-                "update_url": "https://healthchecks.io/api/v1/checks/abc123-def456"
-            }"#,
-            )
-            .create();
+    fn test_config_parse() {
+        let config = Config::parse(r#"
+            [profiles.default]
+            api_key = "key"
+
+            [profiles.selfhosted]
+            api_key = "other-key"
+            read_only_key = "ro-key"
+            ping_key = "ping-key"
+            api_url = "https://hc.example.com/api/v3"
+            ping_url = "https://hc.example.com/ping"
+            retries = 5
+        "#).unwrap();
+
+        assert_eq!(config.profile("default").api_key.as_deref(), Some("key"));
+        assert_eq!(config.profile("default").checks_url(), None);
+
+        let profile = config.profile("selfhosted");
+        assert_eq!(profile.read_only_key.as_deref(), Some("ro-key"));
+        assert_eq!(profile.ping_key.as_deref(), Some("ping-key"));
+        assert_eq!(profile.checks_url().as_deref(), Some("https://hc.example.com/api/v3/checks/"));
+        assert_eq!(profile.ping_base_url().as_deref(), Some("https://hc.example.com/ping/"));
+        assert_eq!(profile.retries, Some(5));
+
+        assert_eq!(config.profile("missing"), Profile::default());
+    }
 
-        let base_url = format!("{}/", server.url());
-        let client = ApiClient::new("test-key", Some(&base_url));
-        //let client = ApiClient::new(&base_url, "test-key");
-        let check = create_test_check("abc123-def456");
+    #[test]
+    fn test_config_parse_invalid() {
+        assert!(Config::parse("[profiles.default]\napi_key = 1").is_err());
+    }
 
-        let result = client.pause(&check);
-        mock.assert();
-        assert!(result.is_ok());
-        let paused_check = result.unwrap();
-        assert_eq!(paused_check.status, "paused");
+    #[test]
+    fn test_active_profile() {
+        assert_eq!(active_profile(Some("work")), "work");
     }
 
     #[test]
-    fn test_api_client_get() {
-        let mut server = Server::new();
-        let mock = server
-            .mock("GET", "/")
-            .match_header("X-Api-Key", "test-key")
-            .with_status(200)
-            .with_header("content-type", "application/json")
-            .with_body(sample_checks_response())
-            .create();
+    fn test_write_private() {
+        let dir = std::env::temp_dir().join(format!("hchk-config-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        std::fs::write(&path, "old contents, readable by others").unwrap();
+
+        write_private(&path, b"api_key").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"api_key");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
 
-        let client = ApiClient::new("test-key", Some(&server.url()));
-        let result = client.get(None);
+// Unit tests for machine-readable output
+#[cfg(test)]
+mod output_tests {
+    use hchk::{Check, ScheduleKind};
+    use crate::output::*;
+
+    fn checks() -> Vec<Check> {
+        let mut backup = super::check("abc123-def456", "backup, nightly");
+        backup.slug = "backup-nightly".to_string();
+        backup.tags = "prod db".to_string();
+        backup.schedule = ScheduleKind::Cron { expr: "0 * * * *".to_string(), tz: "UTC".to_string() };
+
+        let mut other = super::check("xyz789-ghi012", "other");
+        other.grace = 60;
+        other.n_pings = 3;
+        other.schedule = ScheduleKind::Simple { timeout: 86400 };
+        other.status = "down".to_string();
+        vec![backup, other]
+    }
 
-        mock.assert();
-        assert!(result.is_ok());
-        let checks = result.unwrap();
-        assert_eq!(checks.len(), 1);
-        assert_eq!(checks[0].name, "test-check");
-        assert_eq!(checks[0].short_uuid, "abc123");
+    fn render(format: OutputFormat) -> String {
+        let mut out = Vec::new();
+        write_checks(&mut out, &checks(), format).unwrap();
+        String::from_utf8(out).unwrap()
     }
 
     #[test]
-    fn test_api_client_get_with_query() {
-        let mut server = Server::new();
-        let response = r#"{"checks": [
-            {
-                "uuid": "abc123-def456",
-                "name": "test-check-1",
-                "slug": "test-check-1",
-                "ping_url": "https://hc-ping.com/abc123-def456",
-                "pause_url": "",
-                "last_ping": null,
-                "next_ping": null,
-                "grace": 3600,
-                "n_pings": 0,
-                "tags": "",
-                "timeout": null,
-                "tz": null,
-                "schedule": null,
-                "status": "up",
-                "update_url": ""
-            },
-            {
-                "uuid": "xyz789-ghi012",
-                "name": "other-check",
-                "slug": "other-check",
-                "ping_url": "https://hc-ping.com/xyz789-ghi012",
-                "pause_url": "",
-                "last_ping": null,
-                "next_ping": null,
-                "grace": 3600,
-                "n_pings": 0,
-                "tags": "",
-                "timeout": null,
-                "tz": null,
-                "schedule": null,
-                "status": "up",
-                "update_url": ""
-            }
-        ]}"#;
+    fn test_output_json() {
+        let v: serde_json::Value = serde_json::from_str(&render(OutputFormat::Json)).unwrap();
+        assert_eq!(v.as_array().unwrap().len(), 2);
+        assert_eq!(v[1]["status"], "down");
+    }
 
-        let mock = server
-            .mock("GET", "/")
-            .match_header("X-Api-Key", "test-key")
-            .with_status(200)
-            .with_header("content-type", "application/json")
-            .with_body(response)
-            .create();
+    #[test]
+    fn test_output_ndjson() {
+        let out = render(OutputFormat::Ndjson);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let v: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(v["slug"], "backup-nightly");
+    }
 
-        let client = ApiClient::new("test-key", Some(&server.url()));
-        let result = client.get(Some("test"));
+    #[test]
+    fn test_output_csv() {
+        let out = render(OutputFormat::Csv);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("uuid,slug,name,desc,status,tags,grace,timeout,schedule,tz"));
+        assert!(lines[1].starts_with(r#"abc123-def456,backup-nightly,"backup, nightly",,up,prod db,3600,,0 * * * *,UTC"#));
+        assert!(lines[2].starts_with("xyz789-ghi012,other,other,,down,,60,86400,,,3"));
+    }
 
-        mock.assert();
-        assert!(result.is_ok());
-        let checks = result.unwrap();
-        // Should only return checks matching "test"
-        assert_eq!(checks.len(), 1);
-        assert_eq!(checks[0].name, "test-check-1");
+    #[test]
+    fn test_output_yaml() {
+        let out = render(OutputFormat::Yaml);
+        assert!(out.starts_with("- uuid: abc123-def456\n"));
+        assert!(out.contains("- uuid: xyz789-ghi012\n"));
     }
 
     #[test]
-    fn test_api_client_find_success() {
-        let mut server = Server::new();
-        let mock = server
-            .mock("GET", "/")
-            .match_header("X-Api-Key", "test-key")
-            .with_status(200)
-            .with_header("content-type", "application/json")
-            .with_body(sample_checks_response())
-            .create();
+    fn test_format_check() {
+        let mut checks = checks();
+        checks[0].set_short_uuid();
 
-        let client = ApiClient::new("test-key", Some(&server.url()));
-        let result = client.find("test-check");
+        let out = format_check(&checks[0], r"{id}\t{name}\t{ping_url} {{{n_pings}}}").unwrap();
+        assert_eq!(out, "abc123\tbackup, nightly\thttps://hc-ping.com/abc123-def456 {0}");
+        assert!(format_check(&checks[0], "{nope}").is_err());
+    }
 
-        mock.assert();
-        assert!(result.is_some());
-        let check = result.unwrap();
-        assert_eq!(check.name, "test-check");
+    #[test]
+    fn test_cell() {
+        let checks = checks();
+        assert_eq!(cell(&checks[0], "grace"), "1h 0m");
+        assert_eq!(cell(&checks[0], "last_ping"), "never");
+        assert_eq!(cell(&checks[0], "next_ping"), "-");
+        assert_eq!(cell(&checks[1], "timeout"), "1d 0h");
+        assert_eq!(cell(&checks[0], "tags"), "prod db");
+        assert_eq!(cell(&checks[0], "schedule"), "cron 0 * * * * (UTC)");
+        assert_eq!(cell(&checks[1], "schedule"), "every 1d 0h");
     }
 
     #[test]
-    fn test_api_client_find_not_found() {
-        let mut server = Server::new();
-        let mock = server
-            .mock("GET", "/")
-            .match_header("X-Api-Key", "test-key")
-            .with_status(200)
-            .with_header("content-type", "application/json")
-            .with_body(r#"{"checks": []}"#)
-            .create();
+    fn test_validate_columns() {
+        let ok: Vec<String> = vec!["status".into(), "id".into(), "ping_url".into()];
+        let bad: Vec<String> = vec!["status".into(), "colour".into()];
+        assert!(validate_columns(&ok).is_ok());
+        assert!(validate_columns(&bad).unwrap_err().contains("colour"));
+    }
 
-        let client = ApiClient::new("test-key", Some(&server.url()));
-        let result = client.find("nonexistent");
+    #[test]
+    fn test_fit() {
+        assert_eq!(fit("abc", 5), "abc  ");
+        assert_eq!(fit("abcdef", 4), "abc…");
+        // wide characters take two columns each
+        assert_eq!(fit("日本語のチェック", 7), "日本語…");
+        assert_eq!(fit("日本語のチェック", 8), "日本語… ");
+    }
 
-        mock.assert();
-        assert!(result.is_none());
+    #[test]
+    fn test_column_widths() {
+        let rows = vec![
+            vec!["up".to_string(), "a very long check name here".to_string(), "2 hours ago".to_string()],
+            vec!["down".to_string(), "short".to_string(), "never".to_string()],
+        ];
+
+        assert_eq!(column_widths(&rows, None), vec![4, 27, 11]);
+        assert_eq!(column_widths(&rows, Some(30)), vec![4, 13, 11]);
     }
 
     #[test]
-    fn test_api_client_find_error() {
-        let mut server = Server::new();
-        let mock = server
-            .mock("GET", "/")
-            .match_header("X-Api-Key", "test-key")
-            .with_status(500)
-            .create();
+    fn test_output_table_writes_nothing() {
+        assert!(render(OutputFormat::Table).is_empty());
+    }
+}
 
-        let client = ApiClient::new("test-key", Some(&server.url()));
-        let result = client.find("test");
+// Unit tests for the query language
+#[cfg(test)]
+mod filter_tests {
+    use hchk::{Check, ScheduleKind};
+    use crate::filter::*;
+    use chrono::{Duration, Utc};
+
+    fn check(name: &str, status: &str, tags: &str, schedule: Option<&str>) -> Check {
+        let mut c = super::check(&format!("{}-0000-4000-8000-000000000000", &format!("{:0<8}", name)[..8]), name);
+        c.status = status.to_string();
+        c.tags = tags.to_string();
+        c.n_pings = 12;
+        c.last_ping = Some((Utc::now() - Duration::hours(3)).to_rfc3339());
+        if let Some(expr) = schedule {
+            c.schedule = ScheduleKind::from_expr(expr, "UTC");
+        }
+        c
+    }
 
-        mock.assert();
-        assert!(result.is_none());
+    fn matching(query: &str) -> Vec<String> {
+        let checks = vec![
+            check("backup-db", "down", "prod db", Some("0 * * * *")),
+            check("backup-www", "up", "prod", Some("0 3 * * *")),
+            check("cleanup", "paused", "staging", None),
+        ];
+        let filter = Filter::parse(query).unwrap();
+        checks.into_iter().filter(|c| filter.matches(c)).map(|c| c.name).collect()
     }
 
     #[test]
-    fn test_api_client_add_empty_name() {
-        let server = Server::new();
-        let client = ApiClient::new("test-key", Some(&server.url()));
-        let result = client.add("", "0 * * * *", 1, None, None);
-
-        assert!(result.is_err());
-        assert!(
-            result
-                .unwrap_err()
-                .to_string()
-                .contains("name cannot be empty")
-        );
+    fn test_filter_terms() {
+        assert_eq!(matching("status:down,paused"), vec!["backup-db", "cleanup"]);
+        assert_eq!(matching("tag:db"), vec!["backup-db"]);
+        assert_eq!(matching("name~^backup-"), vec!["backup-db", "backup-www"]);
+        assert_eq!(matching(r#"schedule:"0 * * * *""#), vec!["backup-db"]);
+        assert_eq!(matching("paused:true"), vec!["cleanup"]);
+        assert_eq!(matching("last_ping>2h"), vec!["backup-db", "backup-www", "cleanup"]);
+        assert_eq!(matching("last_ping<2h").len(), 0);
+        assert_eq!(matching("n_pings>10").len(), 3);
+        assert_eq!(matching("uuid:clea"), vec!["cleanup"]);
+        assert_eq!(matching("www"), vec!["backup-www"]);
+        assert_eq!(matching("").len(), 3);
     }
 
     #[test]
-    fn test_api_client_add_invalid_grace_zero() {
-        let server = Server::new();
-        let client = ApiClient::new("test-key", Some(&server.url()));
-        let result = client.add("test", "0 * * * *", 0, None, None);
+    fn test_filter_boolean() {
+        assert_eq!(matching("tag:prod status:up"), vec!["backup-www"]);
+        assert_eq!(matching("tag:prod AND NOT status:up"), vec!["backup-db"]);
+        assert_eq!(matching("-tag:prod"), vec!["cleanup"]);
+        assert_eq!(matching("status:up OR paused:true"), vec!["backup-www", "cleanup"]);
+        assert_eq!(matching("(tag:db OR tag:staging) NOT paused:true"), vec!["backup-db"]);
+    }
 
-        assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("Grace period"));
+    #[test]
+    fn test_filter_errors() {
+        assert!(Filter::parse("colour:red").is_err());
+        assert!(Filter::parse("name~(").is_err());
+        assert!(Filter::parse("last_ping>soon").is_err());
+        assert!(Filter::parse("(tag:prod").is_err());
+        assert!(Filter::parse("tag:prod )").is_err());
+        assert!(Filter::parse(r#"name:"backup"#).is_err());
+        assert!(Filter::parse("status:").is_err());
     }
 
     #[test]
-    fn test_api_client_add_invalid_grace_too_large() {
-        let server = Server::new();
-        let client = ApiClient::new("test-key", Some(&server.url()));
-        let result = client.add("test", "0 * * * *", 24 * 366, None, None);
+    fn test_filter_is_selector() {
+        assert!(!Filter::parse("backup").unwrap().is_selector());
+        assert!(!Filter::parse("").unwrap().is_selector());
+        assert!(Filter::parse("tag:prod").unwrap().is_selector());
+        assert!(Filter::parse("backup OR cleanup").unwrap().is_selector());
+    }
 
-        assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("Grace period"));
+    #[test]
+    fn test_filter_from_args() {
+        let args = vec!["tag:prod".to_string(), "schedule:0 * * * *".to_string()];
+        let filter = Filter::from_args(&args).unwrap();
+        assert!(filter.matches(&check("backup-db", "down", "prod db", Some("0 * * * *"))));
+        assert!(!filter.matches(&check("backup-www", "up", "prod", Some("0 3 * * *"))));
     }
 
     #[test]
-    fn test_check_humanized_last_ping_never() {
-        let mut check = create_test_check("abc123-def456");
-        check.short_uuid = "abc123".to_string();
+    fn test_filter_from_args_whole_query() {
+        let checks = [
+            check("backup-db", "down", "prod db", Some("0 * * * *")),
+            check("backup-www", "up", "prod", Some("0 3 * * *")),
+            check("cleanup", "paused", "staging", None),
+        ];
+        let matching = |args: &[&str]| -> Vec<String> {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            let filter = Filter::from_args(&args).unwrap();
+            checks.iter().filter(|c| filter.matches(c)).map(|c| c.name.clone()).collect()
+        };
+
+        assert_eq!(matching(&["tag:db OR tag:staging"]), vec!["backup-db", "cleanup"]);
+        assert_eq!(matching(&["tag:prod -status:up"]), vec!["backup-db"]);
+        assert_eq!(matching(&["last_ping>2h AND NOT paused:true"]), vec!["backup-db", "backup-www"]);
+        // each argument is a group of its own
+        assert_eq!(matching(&["tag:db OR tag:staging", "status:down"]), vec!["backup-db"]);
+        assert!(!Filter::from_args(&["backup nightly".to_string()]).unwrap().is_selector());
+    }
+}
 
-        let humanized = check.humanized_last_ping_at();
-        assert_eq!(humanized, "never");
+// Unit tests for the offline ping spool
+#[cfg(test)]
+mod spool_tests {
+    use std::env;
+    use std::fs;
+    use chrono::{Local, TimeDelta};
+    use hchk::{ApiClient, PingKind};
+    use mockito::{Matcher, Server};
+    use uuid::Uuid;
+    use crate::spool::*;
+
+    fn temp_spool() -> Spool {
+        let dir = env::temp_dir().join(format!("hchk-spool-{}", Uuid::new_v4()));
+        Spool::new(dir, TimeDelta::days(1))
     }
 
     #[test]
-    fn test_api_client_add_unauthorized() {
+    fn test_spool_flush() {
         let mut server = Server::new();
-        let mock = server
-            .mock("POST", "/")
-            .match_header("X-Api-Key", "test-key")
-            .with_status(401)
-            .with_body("Unauthorized")
+        let a = server
+            .mock("POST", "/a/start")
+            .match_body(Matcher::Regex("^\\[hchk: ping delayed, originally sent at .*\\]\n$".to_string()))
+            .with_status(200)
+            .create();
+        let b = server
+            .mock("POST", "/b")
+            .match_body(Matcher::Regex("^job output\n\\[hchk: ping delayed".to_string()))
+            .with_status(200)
             .create();
 
-        let client = ApiClient::new("test-key", Some(&server.url()));
-        let result = client.add("test-check", "0 * * * *", 1, None, None);
-
-        mock.assert();
-        assert!(result.is_err());
-        let error_msg = result.unwrap_err().to_string();
-        assert!(error_msg.contains("API error"));
+        let spool = temp_spool();
+        let url_a = format!("{}/a", server.url());
+        let url_b = format!("{}/b", server.url());
+
+        let mut expired = SpooledPing::new(&url_b, PingKind::Success, None, None);
+        expired.created = (Local::now() - TimeDelta::days(2)).to_rfc3339();
+        spool.push(&expired).unwrap();
+        spool.push(&SpooledPing::new(&url_a, PingKind::Start, None, None)).unwrap();
+        spool.push(&SpooledPing::new(&url_a, PingKind::Start, None, None)).unwrap();
+        spool.push(&SpooledPing::new(&url_b, PingKind::Success, Some(b"job output"), None)).unwrap();
+        assert_eq!(spool.entries().unwrap().len(), 4);
+
+        let stats = spool.flush(&ApiClient::new("", None).unwrap()).unwrap();
+
+        a.assert();
+        b.assert();
+        assert_eq!(stats, FlushStats { sent: 2, duplicates: 1, expired: 1, dropped: 0, pending: 0, busy: false });
+        assert!(spool.entries().unwrap().is_empty());
+        fs::remove_dir_all(&spool.dir).unwrap();
     }
 
     #[test]
-    fn test_api_client_delete_not_found() {
+    fn test_spool_flush_unreachable() {
         let mut server = Server::new();
         let mock = server
-            .mock("DELETE", "/abc123-def456")
-            .match_header("X-Api-Key", "test-key")
-            .with_status(404)
-            .with_body("Not Found")
+            .mock("POST", "/a")
+            .with_status(503)
             .create();
 
-        let base_url = format!("{}/", server.url());
-        let client = ApiClient::new("test-key", Some(&base_url));
-        let check = create_test_check("abc123-def456");
+        let spool = temp_spool();
+        let url = format!("{}/a", server.url());
+        spool.push(&SpooledPing::new(&url, PingKind::Success, None, None)).unwrap();
+        spool.push(&SpooledPing::new(&url, PingKind::Fail, None, None)).unwrap();
+
+        let stats = spool.flush(&ApiClient::new("", None).unwrap()).unwrap();
 
-        let result = client.delete(&check);
         mock.assert();
-        assert!(result.is_err());
-        let error_msg = result.unwrap_err().to_string();
-        assert!(error_msg.contains("API error"));
+        assert_eq!(stats.pending, 2);
+        let entries = spool.entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].1.kind, PingKind::Success);
+        fs::remove_dir_all(&spool.dir).unwrap();
     }
 
     #[test]
-    fn test_api_client_ping_server_error() {
-        let mut server = Server::new();
-        let ping_url = format!("{}/ping", server.url());
-
-        let mock = server
-            .mock("GET", "/ping")
-            .with_status(500)
-            .with_body("Internal Server Error")
-            .create();
+    fn test_spool_flush_busy() {
+        let spool = temp_spool();
+        spool.push(&SpooledPing::new("http://127.0.0.1:1/a", PingKind::Success, None, None)).unwrap();
+        fs::write(spool.dir.join(".lock"), "").unwrap();
+
+        // left to the running flush, nothing is sent
+        let stats = spool.flush(&ApiClient::new("", None).unwrap()).unwrap();
+        assert_eq!(stats, FlushStats { pending: 1, busy: true, ..FlushStats::default() });
+        assert_eq!(spool.entries().unwrap().len(), 1);
+        fs::remove_dir_all(&spool.dir).unwrap();
+    }
+}
 
-        let client = ApiClient::new("test-key", Some(&server.url()));
-        let mut check = create_test_check("abc123-def456");
-        check.ping_url = ping_url;
+// Unit tests for the cache of checks
+#[cfg(test)]
+mod cache_tests {
+    use std::env;
+    use std::fs;
+    use chrono::TimeDelta;
+    use hchk::{ApiClient, MatchKind};
+    use uuid::Uuid;
+    use crate::cache::*;
+    use super::check;
+
+    fn temp_path() -> std::path::PathBuf {
+        env::temp_dir().join(format!("hchk-cache-{}", Uuid::new_v4())).join("checks-default.json")
+    }
 
-        let result = client.ping(&check);
-        mock.assert();
-        assert!(result.is_err());
-        let error_msg = result.unwrap_err().to_string();
-        assert!(error_msg.contains("API error"));
+    #[test]
+    fn test_cache_resolve() {
+        let path = temp_path();
+        let cache = Cache::new(path.clone(), TimeDelta::minutes(5), "https://healthchecks.io/api/v3/checks/", "key");
+        assert!(cache.checks().is_none());
+
+        cache.store(&[
+            check("0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d", "backup"),
+            check("0a1b2c3d-9999-4a6b-8c7d-9e0f1a2b3c4d", "backup-db"),
+        ]);
+
+        let (c, kind) = cache.resolve("backup-db").unwrap();
+        assert_eq!(c.ping_url, "https://hc-ping.com/0a1b2c3d-9999-4a6b-8c7d-9e0f1a2b3c4d");
+        assert_eq!(c.short_uuid, "0a1b2c3d-9");
+        assert_eq!(kind, MatchKind::Slug);
+
+        // unknown and ambiguous IDs go to the API
+        assert!(cache.resolve("cleanup").is_none());
+        assert!(cache.resolve("0a1b").is_none());
+
+        // so do prefixes and substrings, "-db" may match a check added since
+        assert!(cache.resolve("0a1b2c3d-9").is_none());
+        assert!(cache.resolve("-db").is_none());
+
+        // another project's cache is not used
+        let other = Cache::new(path.clone(), TimeDelta::minutes(5), "https://healthchecks.io/api/v3/checks/", "other-key");
+        assert!(other.resolve("backup-db").is_none());
+
+        cache.invalidate();
+        assert!(cache.resolve("backup-db").is_none());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
     }
 
     #[test]
-    fn test_api_client_pause_forbidden() {
-        let mut server = Server::new();
-        let mock = server
-            .mock("POST", "/abc123-def456/pause")
-            .match_header("X-Api-Key", "test-key")
-            .with_status(403)
-            .with_body("Forbidden")
+    fn test_cached_check_fetched() {
+        let path = temp_path();
+        let cache = Cache::new(path.clone(), TimeDelta::minutes(5), "https://healthchecks.io/api/v3/checks/", "key");
+        let mut paused = check("0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d", "backup");
+        paused.status = "paused".to_string();
+        cache.store(&[paused, check("7f000000-4e5f-4a6b-8c7d-9e0f1a2b3c4d", "cleanup")]);
+
+        // the check was pinged since, unpausing it
+        let mut server = mockito::Server::new();
+        let up = serde_json::to_string(&check("0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d", "backup")).unwrap();
+        let mock = server.mock("GET", "/0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d")
+            .with_status(200)
+            .with_body(up)
             .create();
 
-        let base_url = format!("{}/", server.url());
-        let client = ApiClient::new("test-key", Some(&base_url));
-        let check = create_test_check("abc123-def456");
+        let client = ApiClient::new("key", Some(&format!("{}/", server.url()))).unwrap();
+        let c = crate::resolve_check(&client, &cache, "backup").unwrap();
+        mock.assert();
+        assert_eq!(c.status, "up");
+        assert_eq!(c.short_uuid, "0a1b2c3d");
 
-        let result = client.pause(&check);
+        // deleted since cached, looked up in a fresh listing
+        let mock = server.mock("GET", "/7f000000-4e5f-4a6b-8c7d-9e0f1a2b3c4d").with_status(404).create();
+        let listing = server.mock("GET", "/")
+            .with_status(200)
+            .with_body(r#"{"checks": []}"#)
+            .create();
+        let result = crate::resolve_check(&client, &cache, "cleanup");
         mock.assert();
+        listing.assert();
         assert!(result.is_err());
-        let error_msg = result.unwrap_err().to_string();
-        assert!(error_msg.contains("API error"));
-    }
 
-    #[test]
-    fn test_api_client_get_unauthorized() {
-        let mut server = Server::new();
-        let mock = server
-            .mock("GET", "/")
-            .match_header("X-Api-Key", "test-key")
-            .with_status(401)
-            .with_body("Unauthorized")
+        // renamed since cached and the name taken by a new check, which is the one meant
+        cache.store(&[check("0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d", "backup")]);
+        let renamed = serde_json::to_string(&check("0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d", "backup-old")).unwrap();
+        let mock = server.mock("GET", "/0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d")
+            .with_status(200)
+            .with_body(renamed.clone())
+            .create();
+        let new = serde_json::to_string(&check("7f000000-4e5f-4a6b-8c7d-9e0f1a2b3c4d", "backup")).unwrap();
+        let listing = server.mock("GET", "/")
+            .with_status(200)
+            .with_body(format!(r#"{{"checks": [{}, {}]}}"#, renamed, new))
             .create();
+        let c = crate::resolve_check(&client, &cache, "backup").unwrap();
+        mock.assert();
+        listing.assert();
+        assert_eq!(c.uuid, "7f000000-4e5f-4a6b-8c7d-9e0f1a2b3c4d");
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
 
-        let client = ApiClient::new("test-key", Some(&server.url()));
-        let result = client.get(None);
+    #[test]
+    fn test_cache_expired() {
+        let path = temp_path();
+        let cache = Cache::new(path.clone(), TimeDelta::zero(), "https://healthchecks.io/api/v3/checks/", "key");
+        cache.store(&[check("0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d", "backup")]);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+
+        assert!(cache.checks().is_none());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
 
-        mock.assert();
-        assert!(result.is_err());
-        let error_msg = result.unwrap_err().to_string();
-        assert!(error_msg.contains("API error"));
+    #[test]
+    fn test_cache_disabled() {
+        let cache = Cache::disabled();
+        cache.store(&[check("0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d", "backup")]);
+        assert!(cache.resolve("backup").is_none());
     }
 }
@@ -0,0 +1,1298 @@
+// Tests of the library API against a mock server
+use hchk::api::*;
+use hchk::{Error, RetryPolicy};
+use std::time::Duration;
+use chrono::prelude::*;
+use mockito::{Matcher, Server};
+
+fn sample_check_json() -> String {
+    r#"{
+        "uuid": "abc123-def456",
+        "slug": "test-check",
+        "name": "test-check",
+        "ping_url": "https://hc-ping.com/abc123-def456",
+        "pause_url": "https://healthchecks.io/api/v1/checks/abc123-def456/pause",
+        "last_ping": "2024-01-01T12:00:00+00:00",
+        "next_ping": "2024-01-01T13:00:00+00:00",
+        "grace": 3600,
+        "n_pings": 10,
+        "tags": "test",
+        "timeout": 86400,
+        "tz": "UTC",
+        "schedule": "0 * * * *",
+        "status": "up",
+        "update_url": "https://healthchecks.io/api/v1/checks/abc123-def456"
+    }"#
+    .to_string()
+}
+
+fn sample_checks_response() -> String {
+    format!(r#"{{"checks": [{}]}}"#, sample_check_json())
+}
+
+fn hourly() -> ScheduleKind {
+    ScheduleKind::Cron { expr: "0 * * * *".to_string(), tz: "UTC".to_string() }
+}
+
+fn create_test_check(uuid: &str) -> Check {
+    Check {
+        uuid: uuid.to_string(),
+        unique_key: None,
+        short_uuid: "".to_string(),
+        name: "test".to_string(),
+        slug: "test".to_string(),
+        desc: "".to_string(),
+        ping_url: format!("https://hc-ping.com/{}", uuid),
+        pause_url: "".to_string(),
+        last_ping: None,
+        next_ping: None,
+        grace: 3600,
+        n_pings: 0,
+        tags: "".to_string(),
+        schedule: ScheduleKind::Simple { timeout: 86400 },
+        status: "up".to_string(),
+        update_url: "".to_string(),
+    }
+}
+
+#[test]
+fn test_check_short_uuid() {
+    let mut check = create_test_check("abc123-def456");
+    check.set_short_uuid();
+
+    assert_eq!(check.short_uuid, "abc123");
+}
+
+#[test]
+fn test_check_read_only() {
+    let json = r#"{
+        "name": "backup",
+        "slug": "backup",
+        "tags": "",
+        "desc": "",
+        "grace": 3600,
+        "n_pings": 1,
+        "status": "up",
+        "last_ping": null,
+        "next_ping": null,
+        "unique_key": "a6c7b0a8a66bed0df66abfdab3c77736861703ee",
+        "timeout": 3600
+    }"#;
+
+    let mut check: Check = serde_json::from_str(json).unwrap();
+    check.set_short_uuid();
+
+    assert!(check.uuid.is_empty());
+    assert_eq!(check.short_uuid, "a6c7b0a8");
+    assert_eq!(check.id(), "a6c7b0a8a66bed0df66abfdab3c77736861703ee");
+}
+
+#[test]
+fn test_check_last_ping_at() {
+    let mut check = create_test_check("abc123-def456");
+    check.last_ping = Some("2024-01-15T10:30:00+00:00".to_string());
+
+    let last_ping = check.last_ping_at();
+    assert!(last_ping.year() >= 2024);
+}
+
+#[test]
+fn test_check_last_ping_at_none() {
+    let check = create_test_check("abc123-def456");
+
+    let last_ping = check.last_ping_at();
+    assert_eq!(last_ping.year(), 1901);
+    assert_eq!(last_ping.month(), 1);
+    assert_eq!(last_ping.day(), 1);
+}
+
+#[test]
+#[allow(clippy::len_zero)]
+fn test_check_humanized_last_ping_at() {
+    let mut check = create_test_check("abc123-def456");
+    check.last_ping = Some("2024-01-15T10:30:00+00:00".to_string());
+
+    let humanized = check.humanized_last_ping_at();
+    assert!(humanized.len() > 0);
+}
+
+#[test]
+fn test_api_client_new() {
+    let client = ApiClient::new("test-key", Some("https://example.com/api/")).unwrap();
+    assert_eq!(client.base_url, "https://example.com/api/");
+}
+
+#[test]
+fn test_api_client_add() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("POST", "/")
+        .match_header("X-Api-Key", "test-key")
+        .match_body(Matcher::JsonString(r#"{"grace":3600,"name":"test-check","schedule":"0 * * * *","tags":"","tz":"UTC","unique":["name"]}"#.to_string()))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(sample_check_json())
+        .create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let result = client.add("test-check", &hourly(), 3600, None);
+
+    mock.assert();
+    assert!(result.is_ok());
+    let check = result.unwrap();
+    assert_eq!(check.name, "test-check");
+    assert_eq!(check.status, "up");
+}
+
+#[test]
+fn test_api_client_add_with_tags_and_tz() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("POST", "/")
+        .match_header("X-Api-Key", "test-key")
+        .match_body(Matcher::JsonString(r#"{"grace":7200,"name":"test-check","schedule":"0 * * * *","tags":"prod,critical","tz":"America/New_York","unique":["name"]}"#.to_string()))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(sample_check_json())
+        .create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let schedule = ScheduleKind::Cron { expr: "0 * * * *".to_string(), tz: "America/New_York".to_string() };
+    let result = client.add("test-check", &schedule, 7200, Some("prod,critical"));
+
+    mock.assert();
+    assert!(result.is_ok());
+}
+
+#[test]
+fn test_api_client_add_simple() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("POST", "/")
+        .match_body(Matcher::JsonString(r#"{"grace":900,"name":"test-check","tags":"","timeout":3600,"unique":["name"]}"#.to_string()))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(sample_check_json())
+        .create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let result = client.add("test-check", &ScheduleKind::Simple { timeout: 3600 }, 900, None);
+
+    mock.assert();
+    assert!(result.is_ok());
+}
+
+#[test]
+fn test_api_client_add_oncalendar() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("POST", "/")
+        .match_body(Matcher::JsonString(r#"{"grace":3600,"name":"test-check","schedule":"Mon..Fri 02:00","tags":"","tz":"Europe/Warsaw","unique":["name"]}"#.to_string()))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(sample_check_json())
+        .create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let schedule = ScheduleKind::OnCalendar { expr: "Mon..Fri 02:00".to_string(), tz: "Europe/Warsaw".to_string() };
+    let result = client.add("test-check", &schedule, 3600, None);
+
+    mock.assert();
+    assert!(result.is_ok());
+}
+
+#[test]
+fn test_api_client_add_invalid_schedule() {
+    let server = Server::new();
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+
+    let result = client.add("test", &ScheduleKind::Simple { timeout: 30 }, 3600, None);
+    assert!(matches!(result, Err(Error::Invalid(msg)) if msg.starts_with("Period")));
+
+    let cron = ScheduleKind::Cron { expr: "0 * *".to_string(), tz: "UTC".to_string() };
+    let result = client.add("test", &cron, 3600, None);
+    assert!(matches!(result, Err(Error::Invalid(msg)) if msg.contains("cron")));
+
+    let oncalendar = ScheduleKind::OnCalendar { expr: "Mon..Fry 02:00".to_string(), tz: "UTC".to_string() };
+    let result = client.add("test", &oncalendar, 3600, None);
+    assert!(matches!(result, Err(Error::Invalid(msg)) if msg.contains("OnCalendar")));
+
+    let result = client.add("test", &ScheduleKind::from_expr("0 * * * *", "Nowhere/City"), 3600, None);
+    assert!(matches!(result, Err(Error::Invalid(msg)) if msg.contains("time zone")));
+}
+
+#[test]
+fn test_check_schedule_kind() {
+    let check: Check = serde_json::from_str(&sample_check_json()).unwrap();
+    assert_eq!(check.schedule, hourly());
+
+    let v = serde_json::to_value(&check).unwrap();
+    assert_eq!(v["schedule"], "0 * * * *");
+    assert_eq!(v["tz"], "UTC");
+    assert!(v["timeout"].is_null());
+
+    let json = sample_check_json().replace(r#""schedule": "0 * * * *""#, r#""schedule": "Mon..Fri 02:00""#);
+    let check: Check = serde_json::from_str(&json).unwrap();
+    assert_eq!(check.schedule, ScheduleKind::OnCalendar { expr: "Mon..Fri 02:00".to_string(), tz: "UTC".to_string() });
+
+    let check = create_test_check("abc123-def456");
+    assert_eq!(serde_json::to_value(&check).unwrap()["timeout"], 86400);
+}
+
+#[test]
+fn test_api_client_update() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("POST", "/abc123-def456")
+        .match_header("X-Api-Key", "test-key")
+        .match_body(Matcher::JsonString(r#"{"grace":7200,"tags":"prod"}"#.to_string()))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(sample_check_json())
+        .create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let mut check = create_test_check("abc123-def456");
+    check.update_url = format!("{}/abc123-def456", server.url());
+
+    let patch = CheckPatch {
+        grace: Some(7200),
+        tags: Some("prod".to_string()),
+        ..Default::default()
+    };
+    let result = client.update(&check, patch);
+
+    mock.assert();
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap().short_uuid, "abc123");
+}
+
+#[test]
+fn test_api_client_update_empty_patch() {
+    let server = Server::new();
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let check = create_test_check("abc123-def456");
+
+    let result = client.update(&check, CheckPatch::default());
+    assert!(result.is_err());
+    assert!(result.unwrap_err().to_string().contains("Nothing to update"));
+}
+
+#[test]
+fn test_api_client_update_schedule_and_period() {
+    let server = Server::new();
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let check = create_test_check("abc123-def456");
+
+    let patch = CheckPatch {
+        schedule: Some("0 * * * *".to_string()),
+        timeout: Some(3600),
+        ..Default::default()
+    };
+    assert!(matches!(client.update(&check, patch), Err(Error::Invalid(_))));
+}
+
+#[test]
+fn test_api_client_delete() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("DELETE", "/abc123-def456")
+        .match_header("X-Api-Key", "test-key")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(sample_check_json())
+        .create();
+
+    let base_url = format!("{}/", server.url());
+    let client = ApiClient::new("test-key", Some(&base_url)).unwrap();
+    let check = create_test_check("abc123-def456");
+
+    let result = client.delete(&check);
+    mock.assert();
+    assert!(result.is_ok());
+}
+
+#[test]
+fn test_api_client_ping() {
+    let mut server = Server::new();
+    let ping_url = format!("{}/ping", server.url());
+
+    let mock = server.mock("GET", "/ping").with_status(200).create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    //let client = ApiClient::new(&server.url(), "test-key").unwrap();
+    let mut check = create_test_check("abc123-def456");
+    check.ping_url = ping_url;
+
+    let result = client.ping(&check);
+    mock.assert();
+    assert!(result.is_ok());
+}
+
+#[test]
+fn test_api_client_ping_with_start() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/ping/start")
+        .match_query(Matcher::UrlEncoded("rid".into(), "c4ed8a1b-96ba-4c63-8f6e-2c5e4d3b1a10".into()))
+        .with_status(200)
+        .create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let mut check = create_test_check("abc123-def456");
+    check.ping_url = format!("{}/ping", server.url());
+    let rid = "c4ed8a1b-96ba-4c63-8f6e-2c5e4d3b1a10".parse().unwrap();
+
+    let result = client.ping_with(&check, PingKind::Start, None, Some(rid));
+    mock.assert();
+    assert!(result.is_ok());
+}
+
+#[test]
+fn test_api_client_ping_with_body() {
+    let mut server = Server::new();
+    let exit_mock = server
+        .mock("POST", "/ping/2")
+        .match_body("job output")
+        .with_status(200)
+        .create();
+    let fail_mock = server
+        .mock("POST", "/ping/fail")
+        .match_body("killed")
+        .with_status(200)
+        .create();
+    let log_mock = server
+        .mock("POST", "/ping/log")
+        .match_body("progress")
+        .with_status(200)
+        .create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let mut check = create_test_check("abc123-def456");
+    check.ping_url = format!("{}/ping", server.url());
+
+    assert!(client.ping_with(&check, PingKind::ExitStatus(2), Some(b"job output"), None).is_ok());
+    assert!(client.ping_with(&check, PingKind::Fail, Some(b"killed"), None).is_ok());
+    assert!(client.ping_with(&check, PingKind::Log, Some(b"progress"), None).is_ok());
+    exit_mock.assert();
+    fail_mock.assert();
+    log_mock.assert();
+}
+
+#[test]
+fn test_api_client_ping_url_without_api_key() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d")
+        .match_header("X-Api-Key", Matcher::Missing)
+        .with_status(200)
+        .create();
+
+    let mut client = ApiClient::new("test-key", None).unwrap();
+    client.ping_base_url = format!("{}/", server.url());
+
+    let url = client.uuid_ping_url("0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d");
+    let result = client.ping_url(&url, PingKind::Success, None, None);
+    mock.assert();
+    assert!(result.is_ok());
+}
+
+#[test]
+fn test_api_client_ping_slug_create() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("POST", "/ping-key/backup/fail")
+        .match_query(Matcher::UrlEncoded("create".into(), "1".into()))
+        .match_header("X-Api-Key", Matcher::Missing)
+        .match_body("disk full")
+        .with_status(201)
+        .create();
+
+    let mut client = ApiClient::new("test-key", None).unwrap();
+    client.ping_base_url = format!("{}/", server.url());
+
+    let url = client.slug_ping_url("ping-key", "backup", true);
+    let result = client.ping_url(&url, PingKind::Fail, Some(b"disk full"), None);
+    mock.assert();
+    assert!(result.is_ok());
+}
+
+#[test]
+fn test_slug_ping_url() {
+    let client = ApiClient::new("test-key", None).unwrap();
+    assert_eq!(client.slug_ping_url("key", "backup", false), "https://hc-ping.com/key/backup");
+    assert_eq!(client.slug_ping_url("key", "backup", true), "https://hc-ping.com/key/backup?create=1");
+}
+
+#[test]
+fn test_is_uuid() {
+    assert!(is_uuid("0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d"));
+    assert!(!is_uuid("0a1b2c3d4e5f4a6b8c7d9e0f1a2b3c4d"));
+    assert!(!is_uuid("abc123-def456"));
+    assert!(!is_uuid("backup"));
+}
+
+#[test]
+fn test_api_client_pause() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("POST", "/abc123-def456/pause")
+        .match_header("X-Api-Key", "test-key")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{
+            "uuid": "abc123-def456",
+            "name": "test-check",
+            "slug": "test-check",
+            "ping_url": "https://hc-ping.com/abc123-def456",
+            "pause_url": "https://healthchecks.io/api/v1/checks/abc123-def456/pause",
+            "last_ping": null,
+            "next_ping": null,
+            "grace": 3600,
+            "n_pings": 0,
+            "tags": "",
+            "timeout": null,
+            "tz": null,
+            "schedule": null,
+            "status": "paused",
+            "update_url": "https://healthchecks.io/api/v1/checks/abc123-def456"
+        }"#,
+        )
+        .create();
+
+    let base_url = format!("{}/", server.url());
+    let client = ApiClient::new("test-key", Some(&base_url)).unwrap();
+    //let client = ApiClient::new(&base_url, "test-key").unwrap();
+    let check = create_test_check("abc123-def456");
+
+    let result = client.pause(&check);
+    mock.assert();
+    assert!(result.is_ok());
+    let paused_check = result.unwrap();
+    assert_eq!(paused_check.status, "paused");
+}
+
+#[test]
+fn test_api_client_resume() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("POST", "/abc123-def456/resume")
+        .match_header("X-Api-Key", "test-key")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(sample_check_json())
+        .create();
+
+    let base_url = format!("{}/", server.url());
+    let client = ApiClient::new("test-key", Some(&base_url)).unwrap();
+    let mut check = create_test_check("abc123-def456");
+    check.status = "paused".to_string();
+
+    let result = client.resume(&check);
+    mock.assert();
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap().status, "up");
+}
+
+#[test]
+fn test_check_has_tag() {
+    let mut check = create_test_check("abc123-def456");
+    check.tags = "prod db".to_string();
+
+    assert!(check.has_tag("prod"));
+    assert!(check.has_tag("db"));
+    assert!(!check.has_tag("pro"));
+}
+
+#[test]
+fn test_api_client_pings() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/abc123-def456/pings/")
+        .match_header("X-Api-Key", "test-key")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"pings": [
+            {
+                "type": "fail",
+                "date": "2024-01-01T12:00:05+00:00",
+                "n": 2,
+                "scheme": "https",
+                "remote_addr": "192.0.2.1",
+                "method": "POST",
+                "ua": "hchk",
+                "duration": 5.2,
+                "rid": "c4ed8a1b-96ba-4c63-8f6e-2c5e4d3b1a10",
+                "exitstatus": 1
+            },
+            {
+                "type": "start",
+                "date": "2024-01-01T12:00:00+00:00",
+                "n": 1,
+                "scheme": "https",
+                "remote_addr": "192.0.2.1",
+                "method": "GET",
+                "ua": "curl/8.0"
+            }
+        ]}"#,
+        )
+        .create();
+
+    let base_url = format!("{}/", server.url());
+    let client = ApiClient::new("test-key", Some(&base_url)).unwrap();
+    let check = create_test_check("abc123-def456");
+
+    let result = client.pings(&check);
+    mock.assert();
+    assert!(result.is_ok());
+    let pings = result.unwrap();
+    assert_eq!(pings.len(), 2);
+    assert_eq!(pings[0].kind, "fail");
+    assert_eq!(pings[0].exitstatus, Some(1));
+    assert_eq!(pings[1].kind, "start");
+    assert_eq!(pings[1].duration, None);
+    assert_eq!(pings[1].date_at().year(), 2024);
+}
+
+#[test]
+fn test_api_client_ping_body() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/abc123-def456/pings/2/body")
+        .match_header("X-Api-Key", "test-key")
+        .with_status(200)
+        .with_header("content-type", "application/octet-stream")
+        .with_body(b"\x00\xffbinary")
+        .create();
+
+    let base_url = format!("{}/", server.url());
+    let client = ApiClient::new("test-key", Some(&base_url)).unwrap();
+    let check = create_test_check("abc123-def456");
+
+    let result = client.ping_body(&check, 2);
+    mock.assert();
+    assert_eq!(result.unwrap(), b"\x00\xffbinary".to_vec());
+}
+
+#[test]
+fn test_api_client_ping_body_not_found() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/abc123-def456/pings/3/body")
+        .with_status(404)
+        .create();
+
+    let base_url = format!("{}/", server.url());
+    let client = ApiClient::new("test-key", Some(&base_url)).unwrap();
+    let check = create_test_check("abc123-def456");
+
+    let result = client.ping_body(&check, 3);
+    mock.assert();
+    assert!(matches!(result.unwrap_err(), Error::NotFound(_)));
+}
+
+#[test]
+fn test_api_client_flips_read_only() {
+    let mut server = Server::new();
+    let key = "a6c7b0a8a66bed0df66abfdab3c77736861703ee";
+    let mock = server
+        .mock("GET", format!("/{}/flips/", key).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body("[]")
+        .create();
+
+    let base_url = format!("{}/", server.url());
+    let client = ApiClient::new("read-only-key", Some(&base_url)).unwrap();
+    let mut check = create_test_check("");
+    check.unique_key = Some(key.to_string());
+
+    let result = client.flips(&check, None);
+    mock.assert();
+    assert!(result.unwrap().is_empty());
+}
+
+#[test]
+fn test_api_client_flips() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/abc123-def456/flips/")
+        .match_query(Matcher::UrlEncoded("start".into(), "1704067200".into()))
+        .match_header("X-Api-Key", "test-key")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(r#"[
+            {"timestamp": "2024-01-02T10:00:00+00:00", "up": 0},
+            {"timestamp": "2024-01-02T11:00:00+00:00", "up": 1}
+        ]"#)
+        .create();
+
+    let base_url = format!("{}/", server.url());
+    let client = ApiClient::new("test-key", Some(&base_url)).unwrap();
+    let check = create_test_check("abc123-def456");
+    let since = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap().with_timezone(&Local);
+
+    let result = client.flips(&check, Some(since));
+    mock.assert();
+    let flips = result.unwrap();
+    assert_eq!(flips.len(), 2);
+    assert!(!flips[0].up);
+    assert!(flips[1].up);
+}
+
+fn flip(timestamp: &str, up: bool) -> Flip {
+    Flip { timestamp: timestamp.to_string(), up }
+}
+
+#[test]
+fn test_uptime_from_flips() {
+    let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap().with_timezone(&Local);
+    let end = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap().with_timezone(&Local);
+    let flips = vec![
+        flip("2024-01-01T10:00:00+00:00", false),
+        flip("2024-01-01T11:00:00+00:00", true),
+        flip("2024-01-01T20:00:00+00:00", false),
+        flip("2024-01-01T23:00:00+00:00", true),
+    ];
+
+    let uptime = Uptime::from_flips(&flips, start, end, true);
+    assert_eq!(uptime.outages, 2);
+    assert_eq!(uptime.downtime, 4 * 3600);
+    assert_eq!(uptime.longest_outage, 3 * 3600);
+    assert_eq!(uptime.mttr, 2 * 3600);
+    assert!((uptime.uptime - 100.0 * 20.0 / 24.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_uptime_from_flips_down_at_start() {
+    let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap().with_timezone(&Local);
+    let end = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap().with_timezone(&Local);
+    let flips = vec![flip("2024-01-01T06:00:00+00:00", true)];
+
+    let uptime = Uptime::from_flips(&flips, start, end, true);
+    assert_eq!(uptime.outages, 1);
+    assert_eq!(uptime.downtime, 6 * 3600);
+}
+
+#[test]
+fn test_uptime_from_flips_no_flips() {
+    let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap().with_timezone(&Local);
+    let end = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap().with_timezone(&Local);
+
+    assert_eq!(Uptime::from_flips(&[], start, end, true).uptime, 100.0);
+    assert_eq!(Uptime::from_flips(&[], start, end, false).uptime, 0.0);
+}
+
+#[test]
+fn test_api_client_get() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/")
+        .match_header("X-Api-Key", "test-key")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(sample_checks_response())
+        .create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let result = client.get(None);
+
+    mock.assert();
+    assert!(result.is_ok());
+    let checks = result.unwrap();
+    assert_eq!(checks.len(), 1);
+    assert_eq!(checks[0].name, "test-check");
+    assert_eq!(checks[0].short_uuid, "abc123");
+}
+
+#[test]
+fn test_api_client_get_with_query() {
+    let mut server = Server::new();
+    let response = r#"{"checks": [
+        {
+            "uuid": "abc123-def456",
+            "name": "test-check-1",
+            "slug": "test-check-1",
+            "ping_url": "https://hc-ping.com/abc123-def456",
+            "pause_url": "",
+            "last_ping": null,
+            "next_ping": null,
+            "grace": 3600,
+            "n_pings": 0,
+            "tags": "",
+            "timeout": null,
+            "tz": null,
+            "schedule": null,
+            "status": "up",
+            "update_url": ""
+        },
+        {
+            "uuid": "xyz789-ghi012",
+            "name": "other-check",
+            "slug": "other-check",
+            "ping_url": "https://hc-ping.com/xyz789-ghi012",
+            "pause_url": "",
+            "last_ping": null,
+            "next_ping": null,
+            "grace": 3600,
+            "n_pings": 0,
+            "tags": "",
+            "timeout": null,
+            "tz": null,
+            "schedule": null,
+            "status": "up",
+            "update_url": ""
+        }
+    ]}"#;
+
+    let mock = server
+        .mock("GET", "/")
+        .match_header("X-Api-Key", "test-key")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(response)
+        .create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let result = client.get(Some("test"));
+
+    mock.assert();
+    assert!(result.is_ok());
+    let checks = result.unwrap();
+    // Should only return checks matching "test"
+    assert_eq!(checks.len(), 1);
+    assert_eq!(checks[0].name, "test-check-1");
+}
+
+#[test]
+fn test_api_client_get_tagged() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/")
+        .match_header("X-Api-Key", "test-key")
+        .match_query(Matcher::Exact("tag=prod&tag=db".to_string()))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(sample_checks_response())
+        .create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let result = client.get_tagged(Some("test"), &["prod", "db"]);
+
+    mock.assert();
+    let checks = result.unwrap();
+    assert_eq!(checks.len(), 1);
+    assert_eq!(checks[0].name, "test-check");
+}
+
+#[test]
+fn test_api_client_get_tagged_with_query() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/")
+        .match_query(Matcher::Exact("tag=prod".to_string()))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(sample_checks_response())
+        .create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let result = client.get_tagged(Some("other"), &["prod"]);
+
+    mock.assert();
+    assert!(result.unwrap().is_empty());
+}
+
+fn named_check(uuid: &str, name: &str) -> Check {
+    let mut c = create_test_check(uuid);
+    c.name = name.to_string();
+    c.slug = name.to_string();
+    c
+}
+
+fn resolved_name(resolution: Resolution) -> Option<(String, MatchKind)> {
+    match resolution {
+        Resolution::Found(c, kind) => Some((c.name, kind)),
+        _ => None
+    }
+}
+
+#[test]
+fn test_resolve_id() {
+    let checks = vec![
+        named_check("0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d", "backup"),
+        named_check("0a1b2c3d-9999-4a6b-8c7d-9e0f1a2b3c4d", "backup-db"),
+        named_check("7f000000-4e5f-4a6b-8c7d-9e0f1a2b3c4d", "cleanup"),
+    ];
+
+    let found = |id| resolved_name(resolve_id(id, &checks));
+    assert_eq!(found("0a1b2c3d-9999-4a6b-8c7d-9e0f1a2b3c4d"), Some(("backup-db".to_string(), MatchKind::Uuid)));
+    assert_eq!(found("7f00"), Some(("cleanup".to_string(), MatchKind::UuidPrefix)));
+    assert_eq!(found("0a1b2c3d-4e"), Some(("backup".to_string(), MatchKind::UuidPrefix)));
+    // exact slug wins over the substring match of backup-db
+    assert_eq!(found("backup"), Some(("backup".to_string(), MatchKind::Slug)));
+    assert_eq!(found("clean"), Some(("cleanup".to_string(), MatchKind::Substring)));
+    assert!(matches!(resolve_id("nothing", &checks), Resolution::NotFound));
+}
+
+#[test]
+fn test_resolve_id_ambiguous() {
+    let checks = vec![
+        named_check("0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d", "backup-www"),
+        named_check("0a1b2c3d-9999-4a6b-8c7d-9e0f1a2b3c4d", "backup-db"),
+    ];
+
+    match resolve_id("0a1b", &checks) {
+        Resolution::Ambiguous(candidates) => assert_eq!(candidates.len(), 2),
+        r => panic!("expected ambiguous resolution, got {:?}", r)
+    }
+    assert!(matches!(resolve_id("backup", &checks), Resolution::Ambiguous(_)));
+}
+
+#[test]
+fn test_set_short_uuids() {
+    let mut checks = vec![
+        named_check("0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d", "a"),
+        named_check("0a1b2c3d-4e00-4a6b-8c7d-9e0f1a2b3c4d", "b"),
+        named_check("7f000000-4e5f-4a6b-8c7d-9e0f1a2b3c4d", "c"),
+    ];
+    set_short_uuids(&mut checks);
+
+    assert_eq!(checks[0].short_uuid, "0a1b2c3d-4e5");
+    assert_eq!(checks[1].short_uuid, "0a1b2c3d-4e0");
+    assert_eq!(checks[2].short_uuid, "7f000000");
+}
+
+#[test]
+fn test_api_client_resolve_ambiguous() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"checks": [
+            {"uuid": "abc123-1", "name": "backup-db", "slug": "backup-db", "ping_url": "", "pause_url": "",
+             "last_ping": null, "next_ping": null, "grace": 3600, "n_pings": 0, "tags": "",
+             "timeout": null, "tz": null, "schedule": null, "status": "up", "update_url": ""},
+            {"uuid": "abc123-2", "name": "backup-www", "slug": "backup-www", "ping_url": "", "pause_url": "",
+             "last_ping": null, "next_ping": null, "grace": 3600, "n_pings": 0, "tags": "",
+             "timeout": null, "tz": null, "schedule": null, "status": "up", "update_url": ""}
+        ]}"#)
+        .create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let result = client.resolve("backup");
+
+    mock.assert();
+    let msg = result.unwrap_err().to_string();
+    assert!(msg.contains("ambiguous"));
+    assert!(msg.contains("abc123-1 backup-db"));
+    assert!(msg.contains("abc123-2 backup-www"));
+}
+
+#[test]
+fn test_api_client_find_success() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/")
+        .match_header("X-Api-Key", "test-key")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(sample_checks_response())
+        .create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let result = client.find("test-check");
+
+    mock.assert();
+    let check = result.unwrap().unwrap();
+    assert_eq!(check.name, "test-check");
+}
+
+#[test]
+fn test_api_client_find_by_uuid() {
+    let uuid = "0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d";
+    let mut server = Server::new();
+    let list = server.mock("GET", "/").expect(0).create();
+    let mock = server
+        .mock("GET", format!("/{}", uuid).as_str())
+        .match_header("X-Api-Key", "test-key")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(format!(r#"{{"uuid": "{}", "name": "backup", "slug": "backup", "ping_url": "", "pause_url": "",
+            "last_ping": null, "next_ping": null, "grace": 3600, "n_pings": 0, "tags": "",
+            "timeout": null, "tz": null, "schedule": null, "status": "up", "update_url": ""}}"#, uuid))
+        .create();
+
+    let base_url = format!("{}/", server.url());
+    let client = ApiClient::new("test-key", Some(&base_url)).unwrap();
+    let check = client.find(uuid).unwrap().unwrap();
+
+    mock.assert();
+    list.assert();
+    assert_eq!(check.name, "backup");
+    assert_eq!(check.short_uuid, "0a1b2c3d");
+}
+
+#[test]
+fn test_api_client_get_check_not_found() {
+    let key = "a".repeat(40);
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", format!("/{}", key).as_str())
+        .with_status(404)
+        .create();
+
+    let base_url = format!("{}/", server.url());
+    let client = ApiClient::new("test-key", Some(&base_url)).unwrap();
+    let result = client.resolve(&key);
+
+    mock.assert();
+    assert_eq!(result.unwrap_err().to_string(), format!("{}: not found", key));
+}
+
+#[test]
+fn test_api_client_find_not_found() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/")
+        .match_header("X-Api-Key", "test-key")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"checks": []}"#)
+        .create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let result = client.find("nonexistent");
+
+    mock.assert();
+    assert!(result.unwrap().is_none());
+}
+
+#[test]
+fn test_api_client_find_error() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/")
+        .match_header("X-Api-Key", "test-key")
+        .with_status(500)
+        .create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let result = client.find("test");
+
+    mock.assert();
+    assert!(matches!(result, Err(Error::Server(500, _))));
+}
+
+#[test]
+fn test_api_client_find_unauthorized() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/")
+        .with_status(401)
+        .create();
+
+    let client = ApiClient::new("expired-key", Some(&server.url())).unwrap();
+    let result = client.find("test-check");
+
+    mock.assert();
+    assert!(matches!(result, Err(Error::Unauthorized)));
+}
+
+#[test]
+fn test_api_client_add_empty_name() {
+    let server = Server::new();
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let result = client.add("", &hourly(), 3600, None);
+
+    assert!(result.is_err());
+    assert!(
+        result
+            .unwrap_err()
+            .to_string()
+            .contains("name cannot be empty")
+    );
+}
+
+#[test]
+fn test_api_client_add_invalid_grace_zero() {
+    let server = Server::new();
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let result = client.add("test", &hourly(), 0, None);
+
+    assert!(result.is_err());
+    assert!(result.unwrap_err().to_string().contains("Grace period"));
+}
+
+#[test]
+fn test_api_client_add_invalid_grace_too_large() {
+    let server = Server::new();
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let result = client.add("test", &hourly(), 366 * 24 * 3600, None);
+
+    assert!(result.is_err());
+    assert!(result.unwrap_err().to_string().contains("Grace period"));
+}
+
+#[test]
+fn test_check_humanized_last_ping_never() {
+    let mut check = create_test_check("abc123-def456");
+    check.short_uuid = "abc123".to_string();
+
+    let humanized = check.humanized_last_ping_at();
+    assert_eq!(humanized, "never");
+}
+
+#[test]
+fn test_api_client_add_unauthorized() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("POST", "/")
+        .match_header("X-Api-Key", "test-key")
+        .with_status(401)
+        .with_body("Unauthorized")
+        .create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let result = client.add("test-check", &hourly(), 3600, None);
+
+    mock.assert();
+    assert!(result.is_err());
+    let error_msg = result.unwrap_err().to_string();
+    assert!(error_msg.contains("API error"));
+}
+
+#[test]
+fn test_api_client_delete_not_found() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("DELETE", "/abc123-def456")
+        .match_header("X-Api-Key", "test-key")
+        .with_status(404)
+        .with_body("Not Found")
+        .create();
+
+    let base_url = format!("{}/", server.url());
+    let client = ApiClient::new("test-key", Some(&base_url)).unwrap();
+    let check = create_test_check("abc123-def456");
+
+    let result = client.delete(&check);
+    mock.assert();
+    assert!(matches!(result.unwrap_err(), Error::NotFound(_)));
+}
+
+#[test]
+fn test_api_client_ping_server_error() {
+    let mut server = Server::new();
+    let ping_url = format!("{}/ping", server.url());
+
+    let mock = server
+        .mock("GET", "/ping")
+        .with_status(500)
+        .with_body("Internal Server Error")
+        .create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let mut check = create_test_check("abc123-def456");
+    check.ping_url = ping_url;
+
+    let result = client.ping(&check);
+    mock.assert();
+    assert!(result.is_err());
+    let error_msg = result.unwrap_err().to_string();
+    assert!(error_msg.contains("API error"));
+}
+
+#[test]
+fn test_api_client_pause_forbidden() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("POST", "/abc123-def456/pause")
+        .match_header("X-Api-Key", "test-key")
+        .with_status(403)
+        .with_body("Forbidden")
+        .create();
+
+    let base_url = format!("{}/", server.url());
+    let client = ApiClient::new("test-key", Some(&base_url)).unwrap();
+    let check = create_test_check("abc123-def456");
+
+    let result = client.pause(&check);
+    mock.assert();
+    assert!(result.is_err());
+    let error_msg = result.unwrap_err().to_string();
+    assert!(error_msg.contains("API error"));
+}
+
+#[test]
+fn test_api_client_get_unauthorized() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/")
+        .match_header("X-Api-Key", "test-key")
+        .with_status(401)
+        .with_body("Unauthorized")
+        .create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let result = client.get(None);
+
+    mock.assert();
+    assert!(result.is_err());
+    let error_msg = result.unwrap_err().to_string();
+    assert!(error_msg.contains("API error"));
+}
+
+#[test]
+fn test_api_client_get_rate_limited() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/")
+        .with_status(429)
+        .with_header("Retry-After", "30")
+        .create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let result = client.get(None);
+
+    mock.assert();
+    assert!(matches!(result.unwrap_err(), Error::RateLimited { retry_after: Some(30) }));
+}
+
+#[test]
+fn test_api_client_add_conflict() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("POST", "/")
+        .with_status(409)
+        .with_body("a check with this name already exists")
+        .create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    let result = client.add("test-check", &hourly(), 3600, None);
+
+    mock.assert();
+    match result.unwrap_err() {
+        Error::Conflict(body) => assert!(body.contains("already exists")),
+        e => panic!("expected conflict, got {:?}", e)
+    }
+}
+
+#[test]
+fn test_api_client_error_kinds() {
+    let mut server = Server::new();
+    let unauthorized = server.mock("GET", "/").with_status(401).create();
+
+    let client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    assert!(matches!(client.get(None).unwrap_err(), Error::Unauthorized));
+    unauthorized.assert();
+    unauthorized.remove();
+
+    let server_error = server.mock("GET", "/").with_status(502).with_body("Bad Gateway").create();
+    assert!(matches!(client.get(None).unwrap_err(), Error::Server(502, body) if body == "Bad Gateway"));
+    server_error.assert();
+    server_error.remove();
+
+    let garbage = server.mock("GET", "/").with_status(200).with_body("<html>").create();
+    assert!(matches!(client.get(None).unwrap_err(), Error::Decode(_)));
+    garbage.assert();
+
+    let client = ApiClient::new("test-key", Some("http://127.0.0.1:1/")).unwrap();
+    let err = client.get(None).unwrap_err();
+    assert!(matches!(err, Error::Network(_)));
+    assert!(std::error::Error::source(&err).is_some());
+}
+
+#[test]
+fn test_api_client_invalid_key() {
+    // e.g. read from an environment variable with a trailing newline
+    assert!(matches!(ApiClient::new("test-key\n", None), Err(Error::Invalid(_))));
+    assert!(ApiClient::new("", None).is_ok());
+}
+
+fn fast_retries(retries: u32) -> RetryPolicy {
+    RetryPolicy { retries, base_delay: Duration::ZERO, max_delay: Duration::ZERO }
+}
+
+#[test]
+fn test_retry_policy_delay() {
+    let policy = RetryPolicy::new(3);
+    let server_error = Error::Server(503, String::new());
+
+    let delay = policy.delay(0, &server_error).unwrap();
+    assert!(delay >= Duration::from_millis(250) && delay <= Duration::from_millis(500));
+    let delay = policy.delay(2, &server_error).unwrap();
+    assert!(delay >= Duration::from_secs(1) && delay <= Duration::from_secs(2));
+    assert_eq!(policy.delay(3, &server_error), None);
+
+    assert_eq!(policy.delay(0, &Error::RateLimited { retry_after: Some(20) }), Some(Duration::from_secs(20)));
+    assert_eq!(policy.delay(0, &Error::RateLimited { retry_after: Some(3600) }), None);
+    assert_eq!(policy.delay(0, &Error::Server(400, String::new())), None);
+    assert_eq!(policy.delay(0, &Error::Unauthorized), None);
+    assert_eq!(RetryPolicy::none().delay(0, &server_error), None);
+
+    let capped = RetryPolicy::new(30).delay(20, &server_error).unwrap();
+    assert!(capped <= Duration::from_secs(10));
+}
+
+#[test]
+fn test_api_client_ping_retried() {
+    let mut server = Server::new();
+    let failing = server
+        .mock("GET", "/ping")
+        .with_status(502)
+        .expect(2)
+        .create();
+    let success = server
+        .mock("GET", "/ping")
+        .with_status(200)
+        .create();
+
+    let mut client = ApiClient::new("", None).unwrap();
+    client.retry = fast_retries(3);
+    let mut check = create_test_check("abc123-def456");
+    check.ping_url = format!("{}/ping", server.url());
+
+    let result = client.ping(&check);
+    failing.assert();
+    success.assert();
+    assert!(result.is_ok());
+}
+
+#[test]
+fn test_api_client_get_rate_limited_retried() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/")
+        .with_status(429)
+        .with_header("Retry-After", "0")
+        .expect(3)
+        .create();
+
+    let mut client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    client.retry = fast_retries(2);
+
+    let result = client.get(None);
+    mock.assert();
+    assert!(matches!(result, Err(Error::RateLimited { retry_after: Some(0) })));
+}
+
+#[test]
+fn test_api_client_add_not_retried() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("POST", "/")
+        .with_status(503)
+        .expect(1)
+        .create();
+
+    let mut client = ApiClient::new("test-key", Some(&server.url())).unwrap();
+    client.retry = fast_retries(3);
+
+    let result = client.add("test-check", &hourly(), 3600, None);
+    mock.assert();
+    assert!(matches!(result, Err(Error::Server(503, _))));
+}
@@ -0,0 +1,113 @@
+// Tests of the async client against a mock server
+#![cfg(feature = "async")]
+use hchk::{AsyncApiClient, Check, Error, PingKind, ScheduleKind};
+use mockito::{Matcher, Server};
+use uuid::Uuid;
+
+fn sample_check_json(uuid: &str, name: &str) -> String {
+    format!(r#"{{
+        "uuid": "{uuid}", "slug": "{name}", "name": "{name}",
+        "ping_url": "https://hc-ping.com/{uuid}", "pause_url": "", "update_url": "",
+        "last_ping": null, "next_ping": null, "grace": 3600, "n_pings": 0,
+        "tags": "prod", "timeout": null, "tz": null, "schedule": null, "status": "up"
+    }}"#)
+}
+
+fn create_test_check(uuid: &str) -> Check {
+    serde_json::from_str(&sample_check_json(uuid, "test")).unwrap()
+}
+
+#[tokio::test]
+async fn test_async_get_and_find() {
+    let mut server = Server::new_async().await;
+    let mock = server
+        .mock("GET", "/")
+        .match_header("X-Api-Key", "test-key")
+        .match_query(Matcher::UrlEncoded("tag".into(), "prod".into()))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(format!(r#"{{"checks": [{}, {}]}}"#,
+            sample_check_json("0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d", "backup"),
+            sample_check_json("7f000000-4e5f-4a6b-8c7d-9e0f1a2b3c4d", "backup-db")))
+        .create_async()
+        .await;
+
+    let client = AsyncApiClient::new("test-key", Some(&server.url())).unwrap();
+    let checks = client.get_tagged(None, &["prod"]).await.unwrap();
+    assert_eq!(checks.len(), 2);
+    assert_eq!(checks[1].short_uuid, "7f000000");
+    mock.assert_async().await;
+    mock.remove_async().await;
+
+    let mock = server
+        .mock("GET", "/")
+        .with_status(200)
+        .with_body(format!(r#"{{"checks": [{}]}}"#, sample_check_json("0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d", "backup")))
+        .expect(2)
+        .create_async()
+        .await;
+
+    let check = client.find("backup").await.unwrap().unwrap();
+    assert_eq!(check.name, "backup");
+    assert!(client.find("nothing").await.unwrap().is_none());
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_async_ping_with() {
+    let mut server = Server::new_async().await;
+    let rid = Uuid::new_v4();
+    let mock = server
+        .mock("POST", "/abc123-def456/fail")
+        .match_query(Matcher::UrlEncoded("rid".into(), rid.to_string()))
+        .match_header("X-Api-Key", Matcher::Missing)
+        .match_body("exit 3")
+        .with_status(200)
+        .create_async()
+        .await;
+
+    let client = AsyncApiClient::new("test-key", Some(&server.url())).unwrap();
+    let mut check = create_test_check("abc123-def456");
+    check.ping_url = format!("{}/abc123-def456", server.url());
+
+    client.ping_with(&check, PingKind::Fail, Some(b"exit 3"), Some(rid)).await.unwrap();
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_async_pause() {
+    let mut server = Server::new_async().await;
+    let mock = server
+        .mock("POST", "/abc123-def456/pause")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(sample_check_json("abc123-def456", "test"))
+        .create_async()
+        .await;
+
+    let base_url = format!("{}/", server.url());
+    let client = AsyncApiClient::new("test-key", Some(&base_url)).unwrap();
+    let check = client.pause(&create_test_check("abc123-def456")).await.unwrap();
+
+    mock.assert_async().await;
+    assert_eq!(check.short_uuid, "abc123");
+}
+
+#[tokio::test]
+async fn test_async_errors() {
+    let mut server = Server::new_async().await;
+    let mock = server
+        .mock("GET", "/0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d")
+        .with_status(404)
+        .create_async()
+        .await;
+
+    let base_url = format!("{}/", server.url());
+    let client = AsyncApiClient::new("test-key", Some(&base_url)).unwrap();
+    let result = client.get_check("0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d").await;
+    mock.assert_async().await;
+    assert!(matches!(result, Err(Error::NotFound(id)) if id == "0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d"));
+
+    let result = client.add("", &ScheduleKind::Simple { timeout: 3600 }, 3600, None).await;
+    assert!(matches!(result, Err(Error::Invalid(_))));
+}
@@ -0,0 +1,66 @@
+// Tests of OnCalendar expressions parsing and evaluation
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use hchk::{Error, OnCalendar};
+
+fn utc(s: &str) -> DateTime<Utc> {
+    s.parse().unwrap()
+}
+
+fn next(expr: &str, after: &str) -> String {
+    OnCalendar::parse(expr).unwrap()
+        .next_after(utc(after), Tz::UTC)
+        .unwrap()
+        .to_rfc3339()
+}
+
+#[test]
+fn test_oncalendar_weekdays() {
+    // 2026-10-16 is Friday
+    assert_eq!(next("Mon..Fri 02:00", "2026-10-16T01:00:00Z"), "2026-10-16T02:00:00+00:00");
+    assert_eq!(next("Mon..Fri 02:00", "2026-10-16T02:00:00Z"), "2026-10-19T02:00:00+00:00");
+    assert_eq!(next("Sat,Sun *-*-* 10:30", "2026-10-16T12:00:00Z"), "2026-10-17T10:30:00+00:00");
+    assert_eq!(next("Sat..Mon", "2026-10-18T12:00:00Z"), "2026-10-19T00:00:00+00:00");
+}
+
+#[test]
+fn test_oncalendar_dates_and_times() {
+    assert_eq!(next("*-*-01 06:00:00", "2026-10-16T00:00:00Z"), "2026-11-01T06:00:00+00:00");
+    assert_eq!(next("*-02-29", "2026-01-01T00:00:00Z"), "2028-02-29T00:00:00+00:00");
+    assert_eq!(next("2027-01-15 12:00", "2026-10-16T00:00:00Z"), "2027-01-15T12:00:00+00:00");
+    assert_eq!(next("*:0/15", "2026-10-16T10:16:00Z"), "2026-10-16T10:30:00+00:00");
+    assert_eq!(next("*-*-* 8..10:00", "2026-10-16T10:00:00Z"), "2026-10-17T08:00:00+00:00");
+}
+
+#[test]
+fn test_oncalendar_shorthands() {
+    assert_eq!(next("daily", "2026-10-16T10:00:00Z"), "2026-10-17T00:00:00+00:00");
+    assert_eq!(next("weekly", "2026-10-16T10:00:00Z"), "2026-10-19T00:00:00+00:00");
+    assert_eq!(next("monthly", "2026-10-16T10:00:00Z"), "2026-11-01T00:00:00+00:00");
+    assert_eq!(next("hourly", "2026-10-16T10:00:00Z"), "2026-10-16T11:00:00+00:00");
+}
+
+#[test]
+fn test_oncalendar_time_zones() {
+    // CEST is UTC+2, CET UTC+1 after 2026-10-25
+    assert_eq!(next("*-*-* 02:00 Europe/Warsaw", "2026-10-16T10:00:00Z"), "2026-10-17T00:00:00+00:00");
+
+    let warsaw: Tz = "Europe/Warsaw".parse().unwrap();
+    let times = OnCalendar::parse("daily").unwrap().upcoming(utc("2026-10-24T12:00:00Z"), warsaw, 2);
+    assert_eq!(times, vec![utc("2026-10-24T22:00:00Z"), utc("2026-10-25T23:00:00Z")]);
+}
+
+#[test]
+fn test_oncalendar_several_lines() {
+    let expr = "Mon 09:00\nFri 17:00";
+    assert_eq!(next(expr, "2026-10-16T10:00:00Z"), "2026-10-16T17:00:00+00:00");
+    assert_eq!(next(expr, "2026-10-16T18:00:00Z"), "2026-10-19T09:00:00+00:00");
+}
+
+#[test]
+fn test_oncalendar_invalid() {
+    for expr in ["", "Mon..Fry", "*-13-01", "25:00", "0 3 * * *", "*-*~01", "Mon 02:00 Nowhere/City"] {
+        assert!(matches!(OnCalendar::parse(expr), Err(Error::Invalid(_))), "{:?} should be invalid", expr);
+    }
+    assert!(OnCalendar::parse("2001-01-01").unwrap().next_after(Utc::now(), Tz::UTC).is_none());
+}